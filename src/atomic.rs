@@ -0,0 +1,59 @@
+//! Writing files without ever leaving a half written one behind.
+
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{backup::Journal, error::{Error, Result}};
+
+/// lets `write` create the file at a temporary path next to `path`, then renames it over `path`.
+/// If writing fails or the process gets interrupted, whatever was at `path` before is still intact,
+/// which is what makes overwriting the input files safe.
+/// With a `journal`, a file that is already at `path` gets copied into the backup right before the rename.
+pub(crate) fn write_atomically(path: &Path, journal: Option<&Journal>, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let temp_path = get_temp_path(path);
+    let result = write(&temp_path)
+        // the data has to be on disk before the rename, or a crash could still leave an empty file.
+        .and_then(|_| fs::File::open(&temp_path).and_then(|file| file.sync_all()).map_err(|e| Error::io(&temp_path, e)))
+        .and_then(|_| match journal {
+            Some(journal) if path.exists() => journal.back_up(path, true),
+            Some(journal) => journal.record_created(path),
+            None => Ok(()),
+        });
+
+    if result.is_err() {
+        fs::remove_file(&temp_path).unwrap_or(());
+        return result;
+    }
+    fs::rename(&temp_path, path).map_err(|e| {
+        fs::remove_file(&temp_path).unwrap_or(());
+        Error::io(path, e)
+    })
+}
+
+/// **Returns** the temporary path that gets written before renaming it to `path`, in the same directory
+/// so the rename can't cross file systems.
+fn get_temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[test]
+fn failed_write_keeps_original() {
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_atomic_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.wav");
+    fs::write(&path, b"original").unwrap();
+
+    let result = write_atomically(&path, None, |temp| {
+        fs::write(temp, b"half").unwrap();
+        Err(Error::encode(temp, "interrupted"))
+    });
+    assert!(result.is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"original");
+    assert!(!get_temp_path(&path).exists());
+
+    write_atomically(&path, None, |temp| fs::write(temp, b"new").map_err(|e| Error::io(temp, e))).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert!(!get_temp_path(&path).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
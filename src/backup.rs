@@ -0,0 +1,306 @@
+//! Backups of the files that would otherwise be overwritten or deleted, and undoing a run with them.
+//!
+//! Every run gets its own timestamped folder inside the backup directory. The originals are moved into
+//! its `files` folder, or copied when a new file takes their place, and every change is written into `journal.tsv` right before it happens, one
+//! `action<TAB>path[<TAB>backup]` line each, so [`undo`] can put everything back in reverse order.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::{Error, Result};
+
+const JOURNAL_NAME: &str = "journal.tsv";
+const UNDONE_JOURNAL_NAME: &str = "journal.undone.tsv";
+
+/// The journal of a single run, which also moves the originals into the backup folder.
+pub(crate) struct Journal {
+    dir: PathBuf,
+    /// opened on the first change, so a run that doesn't change anything leaves no folder behind.
+    file: Mutex<Option<fs::File>>,
+}
+
+impl Journal {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Journal { dir, file: Mutex::new(None) }
+    }
+
+    /// **Returns** the folder of this run.
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// writes a line and makes sure it's on disk before the change it describes happens.
+    fn record(&self, action: &str, path: &Path, backup: Option<&Path>) -> Result<()> {
+        let mut line = String::from(action);
+        for p in std::iter::once(path).chain(backup) {
+            let p = p.to_str().ok_or_else(|| Error::Path { path: p.to_path_buf(), message: "the path isn't valid unicode".to_string() })?;
+            line.push('\t');
+            line.push_str(&escape(p));
+        }
+        line.push('\n');
+
+        let journal_path = self.dir.join(JOURNAL_NAME);
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            fs::create_dir_all(&self.dir).map_err(|e| Error::io(&self.dir, e))?;
+            let mut new_file = OpenOptions::new().create(true).append(true).open(&journal_path).map_err(|e| Error::io(&journal_path, e))?;
+            new_file.write_all(b"# wav_optimizer journal, undo with: wav_optimizer undo <this folder>\n").map_err(|e| Error::io(&journal_path, e))?;
+            *file = Some(new_file);
+        }
+        let file = file.as_mut().unwrap();
+        file.write_all(line.as_bytes()).and_then(|_| file.sync_data()).map_err(|e| Error::io(&journal_path, e))
+    }
+
+    /// notes down a new file, which undoing removes again.
+    pub(crate) fn record_created(&self, path: &Path) -> Result<()> {
+        self.record("created", &absolute(path)?, None)
+    }
+
+    /// notes down a new folder, which undoing removes again if it's empty by then.
+    pub(crate) fn record_created_dir(&self, dir: &Path) -> Result<()> {
+        self.record("created_dir", &absolute(dir)?, None)
+    }
+
+    /// moves the file at `path` into the backup folder, `deleted` if it's gone after that.
+    /// A file that gets `replaced` is copied instead and stays in place until the new one is renamed over it.
+    pub(crate) fn back_up(&self, path: &Path, replaced: bool) -> Result<()> {
+        let original = absolute(path)?;
+        // the whole absolute path is kept, so equally named files from different folders can't collide.
+        let mut relative: PathBuf = ["files"].iter().collect();
+        for component in original.components() {
+            match component {
+                Component::Prefix(prefix) => relative.push(prefix.as_os_str().to_string_lossy().replace([':', '\\', '?', '/'], "")),
+                Component::Normal(name) => relative.push(name),
+                _ => (),
+            }
+        }
+        // a file that gets backed up twice in one run keeps both versions.
+        let mut backup = relative.clone();
+        let mut i = 1;
+        while self.dir.join(&backup).exists() {
+            let mut name = relative.file_name().unwrap_or_default().to_os_string();
+            name.push(format!(".{i}"));
+            backup.set_file_name(name);
+            i += 1;
+        }
+
+        self.record(if replaced { "replaced" } else { "deleted" }, &original, Some(&backup))?;
+        if replaced {
+            copy_file(path, &self.dir.join(&backup))
+        } else {
+            move_file(path, &self.dir.join(&backup))
+        }
+    }
+}
+
+/// What [`undo`] did.
+#[derive(Debug, Default)]
+pub struct Undone {
+    /// the journal that got undone.
+    pub journal: PathBuf,
+    /// originals that got moved back from the backup folder.
+    pub restored: Vec<PathBuf>,
+    /// files and folders the run created that got removed.
+    pub removed: Vec<PathBuf>,
+    /// changes that couldn't be undone, the rest is undone regardless.
+    pub errors: Vec<Error>,
+}
+
+/// Undoes a run from its journal, newest change first. `dir` is either the folder of a run or the backup
+/// directory itself, which undoes the newest run in it that isn't undone yet.
+/// The journal is renamed afterwards, so a run can't be undone twice.
+pub fn undo(dir: &Path) -> Result<Undone> {
+    let run_dir = find_run(dir)?;
+    let journal_path = run_dir.join(JOURNAL_NAME);
+    let journal = fs::read_to_string(&journal_path).map_err(|e| Error::io(&journal_path, e))?;
+
+    let mut undone = Undone { journal: journal_path.clone(), ..Undone::default() };
+    for line in journal.lines().rev().filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let fields: Vec<PathBuf> = line.split('\t').skip(1).map(|f| PathBuf::from(unescape(f))).collect();
+        let action = line.split('\t').next().unwrap_or_default();
+        let result = match (action, fields.as_slice()) {
+            ("created", [path]) => match fs::remove_file(path) {
+                Ok(()) => {
+                    undone.removed.push(path.clone());
+                    Ok(())
+                },
+                // it never got written, or was removed by hand since.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(Error::io(path, e)),
+            },
+            // a folder that got other files in it since stays.
+            ("created_dir", [path]) => {
+                if fs::remove_dir(path).is_ok() {
+                    undone.removed.push(path.clone());
+                }
+                Ok(())
+            },
+            ("replaced" | "deleted", [path, backup]) => {
+                let backup = run_dir.join(backup);
+                if backup.exists() {
+                    move_file(&backup, path).map(|_| undone.restored.push(path.clone()))
+                } else {
+                    // the run stopped between writing the journal and moving the file, so the original was never touched.
+                    Ok(())
+                }
+            },
+            _ => Err(Error::Path { path: journal_path.clone(), message: format!("unknown journal line {:?}", line) }),
+        };
+        if let Err(e) = result {
+            undone.errors.push(e);
+        }
+    }
+
+    let undone_path = run_dir.join(UNDONE_JOURNAL_NAME);
+    fs::rename(&journal_path, &undone_path).map_err(|e| Error::io(&journal_path, e))?;
+    undone.journal = undone_path;
+    Ok(undone)
+}
+
+/// **Returns** a new, timestamped folder for a run inside the backup directory `root`, without creating it.
+pub fn new_run_dir(root: &Path) -> PathBuf {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let name = format_timestamp(secs);
+    let mut dir = root.join(&name);
+    let mut i = 2;
+    while dir.exists() {
+        dir = root.join(format!("{name}_{i}"));
+        i += 1;
+    }
+    dir
+}
+
+/// **Returns** the folder of the run to undo, see [`undo`].
+fn find_run(dir: &Path) -> Result<PathBuf> {
+    if dir.join(JOURNAL_NAME).is_file() {
+        return Ok(dir.to_path_buf());
+    }
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+    // the timestamped names sort by time.
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|run| run.join(JOURNAL_NAME).is_file())
+        .max()
+        .ok_or_else(|| Error::Path { path: dir.to_path_buf(), message: "there is no run to undo in this folder".to_string() })
+}
+
+/// renames a file, copying it over when the rename can't cross file systems.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map_err(|e| Error::io(from, e))?;
+    fs::remove_file(from).map_err(|e| Error::io(from, e))
+}
+
+/// hard links a file, or copies it through a temporary file where that isn't possible.
+/// Either way `to` only shows up once it's complete, undo would otherwise restore half a file.
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    }
+    if fs::hard_link(from, to).is_ok() {
+        return Ok(());
+    }
+    let mut temp_name = to.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = to.with_file_name(temp_name);
+    fs::copy(from, &temp_path)
+        .and_then(|_| fs::rename(&temp_path, to))
+        .map_err(|e| {
+            fs::remove_file(&temp_path).unwrap_or(());
+            Error::io(from, e)
+        })
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).map_err(|e| Error::io(path, e))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// `YYYY-MM-DD_HH-MM-SS` in UTC.
+fn format_timestamp(secs: u64) -> String {
+    let (days, time) = ((secs / 86400) as i64, secs % 86400);
+    // days to a civil date, from Howard Hinnant's date algorithms.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+#[test]
+fn timestamps_and_escaping() {
+    assert_eq!(format_timestamp(0), "1970-01-01_00-00-00");
+    assert_eq!(format_timestamp(1_709_210_096), "2024-02-29_12-34-56");
+    let path = "a\\b\tc\nd";
+    assert_eq!(unescape(&escape(path)), path);
+    assert!(!escape(path).contains(['\t', '\n']));
+}
+
+#[test]
+fn undo_restores_everything() {
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_backup_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (kept, deleted, created) = (dir.join("kept.wav"), dir.join("deleted.wav"), dir.join("new/created.wav"));
+    fs::write(&kept, b"kept").unwrap();
+    fs::write(&deleted, b"deleted").unwrap();
+
+    let journal = Journal::new(new_run_dir(&dir.join("backup")));
+    journal.back_up(&kept, true).unwrap();
+    // a replaced file stays in place until the new one is renamed over it.
+    assert_eq!(fs::read(&kept).unwrap(), b"kept");
+    // the new file takes its place like a rename does, writing into it would change the hard linked backup too.
+    fs::remove_file(&kept).unwrap();
+    fs::write(&kept, b"overwritten").unwrap();
+    journal.back_up(&deleted, false).unwrap();
+    journal.record_created_dir(&dir.join("new")).unwrap();
+    fs::create_dir(dir.join("new")).unwrap();
+    journal.record_created(&created).unwrap();
+    fs::write(&created, b"created").unwrap();
+    assert!(!deleted.exists());
+
+    let undone = undo(&dir.join("backup")).unwrap();
+    assert!(undone.errors.is_empty());
+    assert_eq!(undone.restored.len(), 2);
+    assert_eq!(fs::read(&kept).unwrap(), b"kept");
+    assert_eq!(fs::read(&deleted).unwrap(), b"deleted");
+    assert!(!dir.join("new").exists());
+    // the run can't be undone twice.
+    assert!(undo(&dir.join("backup")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,352 @@
+//! Keeping the metadata chunks of a wav file that hound doesn't know about.
+//!
+//! The chunks are read from the source, the sample offsets in them are moved to where the output starts,
+//! and they get appended after the audio of the new file.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::error::{Error, Result};
+
+/// the chunks that describe the audio itself or are only padding, those are written fresh.
+const SKIPPED_CHUNKS: [&[u8; 4]; 7] = [b"fmt ", b"data", b"fact", b"JUNK", b"junk", b"PAD ", b"ds64"];
+
+/// A single RIFF chunk, without its header.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// **Returns** the metadata chunks of the wav file at `path`, in the order they're in.
+/// A broken chunk at the end is ignored, the audio was read fine after all.
+pub(crate) fn read_chunks(path: &Path) -> Result<Vec<Chunk>> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let file_len = file.metadata().map_err(|e| Error::io(path, e))?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut riff = [0_u8; 12];
+    reader.read_exact(&mut riff).map_err(|e| Error::decode(path, e))?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(Error::decode(path, "not a RIFF WAVE file"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= file_len {
+        let mut header = [0_u8; 8];
+        reader.read_exact(&mut header).map_err(|e| Error::io(path, e))?;
+        let id: [u8; 4] = header[0..4].try_into().unwrap();
+        let len = u32_at(&header, 4) as u64;
+        let padded_len = len + (len & 1);
+        if pos + 8 + len > file_len {
+            break;
+        }
+
+        if SKIPPED_CHUNKS.contains(&&id) {
+            reader.seek_relative(padded_len as i64).map_err(|e| Error::io(path, e))?;
+        } else {
+            let mut data = vec![0_u8; len as usize];
+            reader.read_exact(&mut data).map_err(|e| Error::io(path, e))?;
+            reader.seek_relative((padded_len - len) as i64).map_err(|e| Error::io(path, e))?;
+            chunks.push(Chunk { id, data });
+        }
+        pos += 8 + padded_len;
+    }
+    Ok(chunks)
+}
+
+/// writes `chunks` after the end of the wav file at `path` and fixes up the RIFF size.
+pub(crate) fn append_chunks(path: &Path, chunks: &[Chunk]) -> Result<()> {
+    if chunks.is_empty() {
+        return Ok(());
+    }
+    let mut file = File::options().read(true).write(true).open(path).map_err(|e| Error::io(path, e))?;
+    let mut len = file.seek(SeekFrom::End(0)).map_err(|e| Error::io(path, e))?;
+
+    let mut buf = Vec::new();
+    // the audio might end on an odd byte without its padding.
+    if len % 2 == 1 {
+        buf.push(0);
+    }
+    for chunk in chunks {
+        buf.extend_from_slice(&chunk.id);
+        buf.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&chunk.data);
+        if chunk.data.len() % 2 == 1 {
+            buf.push(0);
+        }
+    }
+    len += buf.len() as u64;
+    let riff_len = u32::try_from(len - 8).map_err(|_| Error::encode(path, "the metadata makes the file larger than 4 GB"))?;
+
+    file.write_all(&buf).map_err(|e| Error::io(path, e))?;
+    file.seek(SeekFrom::Start(4)).map_err(|e| Error::io(path, e))?;
+    file.write_all(&riff_len.to_le_bytes()).map_err(|e| Error::io(path, e))
+}
+
+/// **Returns** the chunks for an output that holds `len` frames starting at frame `start` of the source.
+/// Cue points and loops outside of the output are dropped, along with their labels, and the rest are moved.
+pub(crate) fn adjust_chunks(chunks: &[Chunk], start: usize, len: usize) -> Vec<Chunk> {
+    let (start, end) = (start as u64, (start + len) as u64);
+    let mut dropped_cues = Vec::new();
+    let mut adjusted: Vec<Chunk> = chunks.iter().filter_map(|chunk| match &chunk.id {
+        b"cue " => adjust_cue(chunk, start, end, &mut dropped_cues),
+        b"smpl" => Some(adjust_smpl(chunk, start, end)),
+        b"bext" => Some(adjust_bext(chunk, start)),
+        b"iXML" => Some(adjust_ixml(chunk, start)),
+        _ => Some(chunk.clone()),
+    }).collect();
+
+    // the labels of dropped cue points go too, which needs all of the cue chunk first.
+    if !dropped_cues.is_empty() {
+        adjusted.retain_mut(|chunk| !is_adtl(chunk) || drop_labels(chunk, &dropped_cues));
+    }
+    adjusted
+}
+
+/// A named region of a file, for samplers that slice a single file.
+#[derive(Debug, Clone)]
+pub(crate) struct Marker {
+    /// the first frame of the region.
+    pub start: usize,
+    /// the length in frames.
+    pub len: usize,
+    pub label: String,
+}
+
+/// **Returns** a `cue ` chunk with a point at the start of every marker and a `LIST/adtl` chunk with their
+/// labels and lengths (`labl` and `ltxt` with the `rgn ` purpose), the cue ids counting up from 1.
+pub(crate) fn marker_chunks(markers: &[Marker]) -> Vec<Chunk> {
+    let mut cue = (markers.len() as u32).to_le_bytes().to_vec();
+    let mut adtl = b"adtl".to_vec();
+    let mut push_entry = |id: &[u8; 4], data: &[u8]| {
+        adtl.extend_from_slice(id);
+        adtl.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        adtl.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            adtl.push(0);
+        }
+    };
+
+    for (i, marker) in markers.iter().enumerate() {
+        let id = i as u32 + 1;
+        let start = marker.start as u32;
+        for value in [id, start, u32::from_le_bytes(*b"data"), 0, 0, start] {
+            cue.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut label = id.to_le_bytes().to_vec();
+        label.extend_from_slice(marker.label.as_bytes());
+        label.push(0);
+        push_entry(b"labl", &label);
+
+        // id, length, purpose, then country, language, dialect and code page, all unset.
+        let mut region = id.to_le_bytes().to_vec();
+        region.extend_from_slice(&(marker.len as u32).to_le_bytes());
+        region.extend_from_slice(b"rgn ");
+        region.extend_from_slice(&[0; 8]);
+        push_entry(b"ltxt", &region);
+    }
+
+    if markers.is_empty() {
+        return Vec::new();
+    }
+    vec![Chunk { id: *b"cue ", data: cue }, Chunk { id: *b"LIST", data: adtl }]
+}
+
+/// `cue `: a count, then 24 bytes per point: id, position, chunk id, chunk start, block start, sample offset.
+fn adjust_cue(chunk: &Chunk, start: u64, end: u64, dropped: &mut Vec<u32>) -> Option<Chunk> {
+    let data = &chunk.data;
+    if data.len() < 4 {
+        return None;
+    }
+    let count = (u32_at(data, 0) as usize).min((data.len() - 4) / 24);
+    let mut points = Vec::with_capacity(count * 24);
+    for point in data[4..4 + count * 24].chunks_exact(24) {
+        let offset = u32_at(point, 20) as u64;
+        if offset < start || offset >= end {
+            dropped.push(u32_at(point, 0));
+            continue;
+        }
+        let position = (u32_at(point, 4) as u64).saturating_sub(start) as u32;
+        let mut point = point.to_vec();
+        set_u32(&mut point, 4, position);
+        // uncompressed audio is addressed by the sample offset, the old block start wouldn't match anymore.
+        set_u32(&mut point, 16, 0);
+        set_u32(&mut point, 20, (offset - start) as u32);
+        points.extend_from_slice(&point);
+    }
+    if points.is_empty() {
+        return None;
+    }
+
+    let mut new_data = ((points.len() / 24) as u32).to_le_bytes().to_vec();
+    new_data.extend_from_slice(&points);
+    Some(Chunk { id: chunk.id, data: new_data })
+}
+
+/// `smpl`: 36 bytes of header with the loop count at 28, then 24 bytes per loop with its start and end at 8 and 12.
+fn adjust_smpl(chunk: &Chunk, start: u64, end: u64) -> Chunk {
+    let data = &chunk.data;
+    if data.len() < 36 {
+        return chunk.clone();
+    }
+    let count = (u32_at(data, 28) as usize).min((data.len() - 36) / 24);
+    let loops_end = 36 + count * 24;
+
+    let mut new_data = data[..36].to_vec();
+    let mut kept = 0;
+    for sample_loop in data[36..loops_end].chunks_exact(24) {
+        let (loop_start, loop_end) = (u32_at(sample_loop, 8) as u64, u32_at(sample_loop, 12) as u64);
+        // the end is inclusive, a loop that is cut into can't play right anymore.
+        if loop_start < start || loop_end >= end {
+            continue;
+        }
+        let mut sample_loop = sample_loop.to_vec();
+        set_u32(&mut sample_loop, 8, (loop_start - start) as u32);
+        set_u32(&mut sample_loop, 12, (loop_end - start) as u32);
+        new_data.extend_from_slice(&sample_loop);
+        kept += 1;
+    }
+    set_u32(&mut new_data, 28, kept);
+    new_data.extend_from_slice(&data[loops_end..]);
+    Chunk { id: chunk.id, data: new_data }
+}
+
+/// `bext`: the time reference is the first sample in samples since midnight, a u64 at 338.
+fn adjust_bext(chunk: &Chunk, start: u64) -> Chunk {
+    let mut chunk = chunk.clone();
+    if let Some(bytes) = chunk.data.get_mut(338..346) {
+        let time_reference = u64::from_le_bytes(bytes.try_into().unwrap()) + start;
+        bytes.copy_from_slice(&time_reference.to_le_bytes());
+    }
+    chunk
+}
+
+/// `iXML`: the BWF time reference is repeated in the xml as two 32 bit halves.
+fn adjust_ixml(chunk: &Chunk, start: u64) -> Chunk {
+    const HI: &str = "TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_HI";
+    const LO: &str = "TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO";
+    let Ok(xml) = std::str::from_utf8(&chunk.data) else {
+        return chunk.clone();
+    };
+    let (Some(hi), Some(lo)) = (tag_value(xml, HI), tag_value(xml, LO)) else {
+        return chunk.clone();
+    };
+    let (Ok(hi), Ok(lo)) = (hi.trim().parse::<u64>(), lo.trim().parse::<u64>()) else {
+        return chunk.clone();
+    };
+
+    let timestamp = (hi << 32 | lo) + start;
+    let xml = replace_tag_value(xml, HI, &(timestamp >> 32).to_string());
+    let xml = replace_tag_value(&xml, LO, &(timestamp & 0xffff_ffff).to_string());
+    Chunk { id: chunk.id, data: xml.into_bytes() }
+}
+
+pub(crate) fn is_adtl(chunk: &Chunk) -> bool {
+    &chunk.id == b"LIST" && chunk.data.starts_with(b"adtl")
+}
+
+/// removes the `labl`, `note` and `ltxt` entries of `LIST/adtl` that belong to dropped cue points.
+/// **Returns** false if nothing is left.
+fn drop_labels(chunk: &mut Chunk, dropped: &[u32]) -> bool {
+    let mut new_data = b"adtl".to_vec();
+    let mut pos = 4;
+    while pos + 8 <= chunk.data.len() {
+        let len = u32_at(&chunk.data, pos + 4) as usize;
+        let padded_end = (pos + 8 + len + (len & 1)).min(chunk.data.len());
+        let entry = &chunk.data[pos..padded_end];
+        if entry.len() < 12 || !dropped.contains(&u32_at(entry, 8)) {
+            new_data.extend_from_slice(entry);
+        }
+        pos = padded_end;
+    }
+    chunk.data = new_data;
+    chunk.data.len() > 4
+}
+
+fn tag_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let from = xml.find(&open)? + open.len();
+    let to = from + xml[from..].find("</")?;
+    Some(&xml[from..to])
+}
+
+fn replace_tag_value(xml: &str, tag: &str, value: &str) -> String {
+    let open = format!("<{tag}>");
+    let Some(from) = xml.find(&open).map(|i| i + open.len()) else {
+        return xml.to_string();
+    };
+    let to = from + xml[from..].find("</").unwrap_or(0);
+    format!("{}{}{}", &xml[..from], value, &xml[to..])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn set_u32(data: &mut [u8], at: usize, value: u32) {
+    data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+fn cue_point(id: u32, offset: u32) -> Vec<u8> {
+    [id, offset, u32::from_le_bytes(*b"data"), 0, 0, offset].iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn chunks_roundtrip_and_adjust() {
+    let path = std::env::temp_dir().join(format!("wav_optimizer_chunks_{}.wav", std::process::id()));
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 8, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..101 {
+        writer.write_sample(i as i8).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let mut cue = 2_u32.to_le_bytes().to_vec();
+    cue.extend(cue_point(1, 10));
+    cue.extend(cue_point(2, 80));
+    let mut smpl = vec![0_u8; 36];
+    set_u32(&mut smpl, 28, 2);
+    for (loop_start, loop_end) in [(20_u32, 40_u32), (50, 90)] {
+        smpl.extend([0, 0, loop_start, loop_end, 0, 0].iter().flat_map(|v: &u32| v.to_le_bytes()));
+    }
+    let mut bext = vec![0_u8; 602];
+    bext[338..346].copy_from_slice(&1000_u64.to_le_bytes());
+    let mut adtl = b"adtl".to_vec();
+    for (id, text) in [(1_u32, b"one\0"), (2, b"two\0")] {
+        adtl.extend_from_slice(b"labl");
+        adtl.extend_from_slice(&8_u32.to_le_bytes());
+        adtl.extend_from_slice(&id.to_le_bytes());
+        adtl.extend_from_slice(text);
+    }
+    let chunks = vec![
+        Chunk { id: *b"LIST", data: b"INFOINAM\x05\0\0\0kick\0\0".to_vec() },
+        Chunk { id: *b"cue ", data: cue },
+        Chunk { id: *b"smpl", data: smpl },
+        Chunk { id: *b"bext", data: bext },
+        Chunk { id: *b"LIST", data: adtl },
+        Chunk { id: *b"iXML", data: b"<BEXT><TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_HI>1</TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_HI><TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO>4294967295</TIMESTAMP_SAMPLES_SINCE_MIDNIGHT_LO></BEXT>".to_vec() },
+    ];
+    append_chunks(&path, &chunks).unwrap();
+    assert_eq!(read_chunks(&path).unwrap(), chunks);
+    // the audio is still readable.
+    assert_eq!(hound::WavReader::open(&path).unwrap().samples::<i8>().count(), 101);
+    std::fs::remove_file(&path).unwrap();
+
+    // frames 5..=44 of the source, so only the first cue point and loop are in it.
+    let adjusted = adjust_chunks(&chunks, 5, 40);
+    assert_eq!(adjusted.len(), 6);
+    assert_eq!(adjusted[0], chunks[0]);
+    assert_eq!(adjusted[1].data, [1_u32.to_le_bytes().to_vec(), cue_point(1, 5)].concat());
+    assert_eq!((u32_at(&adjusted[2].data, 28), u32_at(&adjusted[2].data, 44), u32_at(&adjusted[2].data, 48)), (1, 15, 35));
+    assert_eq!(adjusted[2].data.len(), 36 + 24);
+    assert_eq!(u64::from_le_bytes(adjusted[3].data[338..346].try_into().unwrap()), 1005);
+    assert_eq!(&adjusted[4].data[..], b"adtllabl\x08\0\0\0\x01\0\0\0one\0");
+    assert!(std::str::from_utf8(&adjusted[5].data).unwrap().contains("_HI>2</") && std::str::from_utf8(&adjusted[5].data).unwrap().contains("_LO>4</"));
+}
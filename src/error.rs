@@ -0,0 +1,96 @@
+//! The errors that can happen while processing a file.
+
+use std::{any::Any, fmt, io, path::{Path, PathBuf}};
+
+/// Everything that can go wrong with a single file, each carrying the path it happened at.
+#[derive(Debug)]
+pub enum Error {
+    /// the file isn't a valid wav file, or its samples couldn't be read.
+    Decode { path: PathBuf, message: String },
+    /// the samples couldn't be written into a wav file.
+    Encode { path: PathBuf, message: String },
+    /// a file system operation failed.
+    Io { path: PathBuf, source: io::Error },
+    /// the file is a wav file, but its sample format isn't supported.
+    UnsupportedFormat { path: PathBuf, format: String },
+    /// the path can't be used, for example because its file name isn't valid unicode.
+    Path { path: PathBuf, message: String },
+    /// processing the file panicked, the other files keep going.
+    Panic { path: PathBuf, message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn decode(path: &Path, err: impl fmt::Display) -> Self {
+        Error::Decode { path: path.to_path_buf(), message: err.to_string() }
+    }
+
+    pub(crate) fn encode(path: &Path, err: impl fmt::Display) -> Self {
+        Error::Encode { path: path.to_path_buf(), message: err.to_string() }
+    }
+
+    pub(crate) fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io { path: path.to_path_buf(), source }
+    }
+
+    /// maps an error from opening or reading a file with hound.
+    pub(crate) fn from_hound_read(path: &Path, err: hound::Error) -> Self {
+        match err {
+            hound::Error::Unsupported => Error::UnsupportedFormat { path: path.to_path_buf(), format: "compressed or unknown sample format".to_string() },
+            hound::Error::IoError(e) if e.kind() == io::ErrorKind::NotFound => Error::io(path, e),
+            err => Error::decode(path, err),
+        }
+    }
+
+    /// maps the payload of a caught panic, which is usually the message given to `panic!`.
+    pub(crate) fn from_panic(path: &Path, payload: &(dyn Any + Send)) -> Self {
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Error::Panic { path: path.to_path_buf(), message }
+    }
+
+    /// **Returns** the path of the file the error happened at.
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Decode { path, .. }
+            | Error::Encode { path, .. }
+            | Error::Io { path, .. }
+            | Error::UnsupportedFormat { path, .. }
+            | Error::Path { path, .. }
+            | Error::Panic { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Decode { path, message } => write!(f, "couldn't read wav {:?}: {}", path, message),
+            Error::Encode { path, message } => write!(f, "couldn't write wav {:?}: {}", path, message),
+            Error::Io { path, source } => write!(f, "{:?}: {}", path, source),
+            Error::UnsupportedFormat { path, format } => write!(f, "{:?}: {} not supported", path, format),
+            Error::Path { path, message } => write!(f, "invalid path {:?}: {}", path, message),
+            Error::Panic { path, message } => write!(f, "{:?}: processing panicked: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn panic_message() {
+    let path = Path::new("a.wav");
+    let payload = std::panic::catch_unwind(|| panic!("index {} out of range", 3)).unwrap_err();
+    assert_eq!(Error::from_panic(path, payload.as_ref()).to_string(), "\"a.wav\": processing panicked: index 3 out of range");
+    let payload = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+    assert!(matches!(Error::from_panic(path, payload.as_ref()), Error::Panic { message, .. } if message == "boom"));
+}
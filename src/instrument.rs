@@ -0,0 +1,280 @@
+//! Sampler instruments that map the auto cuts to keys or velocity layers.
+
+use std::{fmt::Write, path::{Component, Path}};
+
+use crate::{naming::Naming, WavProcessor};
+
+/// How the cuts of a file are spread over an instrument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SliceLayout {
+    /// every cut gets its own key, from the start note up by the note step.
+    Keys,
+    /// every cut is a velocity layer of the start note, the first cut being the softest.
+    Velocity,
+}
+
+impl SliceLayout {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "keys" => Some(SliceLayout::Keys),
+            "velocity" => Some(SliceLayout::Velocity),
+            _ => None,
+        }
+    }
+}
+
+/// Which keys play a cut in the [`SliceLayout::Keys`] layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyRange {
+    /// only the key of the cut.
+    Single,
+    /// the key of the cut up to the one before the next cut.
+    Fill,
+    /// halfway to the neighbouring cuts on both sides.
+    Centered,
+}
+
+impl KeyRange {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "single" => Some(KeyRange::Single),
+            "fill" => Some(KeyRange::Fill),
+            "centered" => Some(KeyRange::Centered),
+            _ => None,
+        }
+    }
+}
+
+/// Loops every cut from `start_ms` into the cut up to its end.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Loop {
+    pub start_ms: f32,
+    pub crossfade_ms: f32,
+}
+
+/// Settings for the instruments written next to the cuts. The start note and step come from the [`Naming`],
+/// so `{note}` in the file names matches the mapping.
+#[derive(Debug)]
+pub struct Instrument {
+    pub layout: SliceLayout,
+    pub key_range: KeyRange,
+    /// the note every cut plays at its original pitch, instead of its own key.
+    pub root_note: Option<u8>,
+    pub looping: Option<Loop>,
+    /// writes an `.sfz` file.
+    pub sfz: bool,
+    /// writes a Decent Sampler `.dspreset` file.
+    pub dspreset: bool,
+}
+
+impl Default for Instrument {
+    fn default() -> Self {
+        Instrument {
+            layout: SliceLayout::Keys,
+            key_range: KeyRange::Fill,
+            root_note: None,
+            looping: None,
+            sfz: false,
+            dspreset: false,
+        }
+    }
+}
+
+impl Instrument {
+    /// **Returns** true if any instrument gets written.
+    pub fn is_enabled(&self) -> bool {
+        self.sfz || self.dspreset
+    }
+}
+
+/// A cut the way an instrument refers to it.
+#[derive(Debug, Clone)]
+pub(crate) struct SliceFile {
+    /// the path of the cut relative to the instrument, with `/` separators.
+    pub path: String,
+    /// the length in frames.
+    pub len: usize,
+}
+
+/// Where a single cut sits in the instrument.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Zone {
+    /// the path of the cut relative to the instrument, with `/` separators.
+    pub sample: String,
+    pub root: u8,
+    pub lokey: u8,
+    pub hikey: u8,
+    pub lovel: u8,
+    pub hivel: u8,
+    /// the first and last frame of the loop, if it loops.
+    pub loop_points: Option<(usize, usize)>,
+    /// the crossfade of the loop in frames.
+    pub loop_crossfade: usize,
+}
+
+/// the most velocity layers a note can have, one per MIDI velocity.
+pub(crate) const MAX_VELOCITY_LAYERS: usize = 127;
+
+/// **Returns** how many cuts fit in the instrument: a key each up to the highest MIDI note,
+/// or [`MAX_VELOCITY_LAYERS`] layers on the start note.
+pub(crate) fn max_zones(naming: &Naming, instrument: &Instrument) -> usize {
+    match instrument.layout {
+        SliceLayout::Keys if naming.note_step == 0 => usize::MAX,
+        SliceLayout::Keys => (127_usize.saturating_sub(naming.start_note as usize)) / naming.note_step as usize + 1,
+        SliceLayout::Velocity => MAX_VELOCITY_LAYERS,
+    }
+}
+
+/// **Returns** the zones of the cuts, in the order of the cuts. Only the first [`max_zones`] cuts get one.
+pub(crate) fn zones(slices: &[SliceFile], naming: &Naming, instrument: &Instrument, sample_rate: u32) -> Vec<Zone> {
+    let slices = &slices[..slices.len().min(max_zones(naming, instrument))];
+    let count = slices.len().max(1);
+    let step = naming.note_step.max(1) as usize;
+    let last = slices.len().saturating_sub(1);
+    let to_frames = |ms: f32| WavProcessor::get_sample_len_from_ms(&ms, &sample_rate) as usize;
+
+    slices.iter().enumerate().map(|(i, slice)| {
+        let key = naming.note(i);
+        let (lokey, hikey, lovel, hivel) = match instrument.layout {
+            SliceLayout::Keys => {
+                let (lokey, hikey) = match instrument.key_range {
+                    KeyRange::Single => (key as usize, key as usize),
+                    // the keys up to the next cut play this one, transposed.
+                    KeyRange::Fill => (key as usize, key as usize + step - 1),
+                    KeyRange::Centered => (
+                        if i == 0 { (key as usize).saturating_sub(step / 2) } else { (naming.note(i - 1) as usize + key as usize) / 2 + 1 },
+                        if i == last { key as usize + step / 2 } else { (key as usize + naming.note(i + 1) as usize) / 2 },
+                    ),
+                };
+                (lokey.min(127) as u8, hikey.min(127) as u8, 1, 127)
+            },
+            SliceLayout::Velocity => (naming.start_note, naming.start_note, (i * 127 / count + 1) as u8, ((i + 1) * 127 / count) as u8),
+        };
+        let root = instrument.root_note.unwrap_or(if instrument.layout == SliceLayout::Keys { key } else { naming.start_note });
+
+        let looping = instrument.looping.filter(|_| slice.len > 1);
+        Zone {
+            sample: slice.path.clone(),
+            root,
+            lokey,
+            hikey,
+            lovel,
+            hivel,
+            loop_points: looping.map(|l| (to_frames(l.start_ms).min(slice.len - 2), slice.len - 1)),
+            loop_crossfade: looping.map_or(0, |l| to_frames(l.crossfade_ms)),
+        }
+    }).collect()
+}
+
+/// **Returns** an sfz instrument with a region per zone, `source` being the name of the file that was cut up.
+pub(crate) fn render_sfz(zones: &[Zone], source: &str, sample_rate: u32) -> String {
+    let mut sfz = format!("// {source}, cut up by wav_optimizer\n\n<group>\n");
+    for zone in zones {
+        write!(sfz, "<region> lokey={} hikey={} pitch_keycenter={}", zone.lokey, zone.hikey, zone.root).unwrap();
+        if zone.lovel != 1 || zone.hivel != 127 {
+            write!(sfz, " lovel={} hivel={}", zone.lovel, zone.hivel).unwrap();
+        }
+        if let Some((loop_start, loop_end)) = zone.loop_points {
+            write!(sfz, " loop_mode=loop_continuous loop_start={} loop_end={}", loop_start, loop_end).unwrap();
+            if zone.loop_crossfade > 0 {
+                write!(sfz, " loop_crossfade={}", zone.loop_crossfade as f64 / sample_rate.max(1) as f64).unwrap();
+            }
+        }
+        // the sample goes last, so spaces in its path don't need escaping.
+        writeln!(sfz, " sample={}", zone.sample).unwrap();
+    }
+    sfz
+}
+
+/// **Returns** a Decent Sampler preset with a `<sample>` per zone, `source` being the name of the file that was cut up.
+pub(crate) fn render_dspreset(zones: &[Zone], source: &str) -> String {
+    let mut preset = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(preset, "<!-- {}, cut up by wav_optimizer -->", source.replace("--", "- -")).unwrap();
+    preset.push_str("<DecentSampler minVersion=\"1.0.0\">\n  <groups>\n    <group>\n");
+    for zone in zones {
+        write!(
+            preset,
+            "      <sample path=\"{}\" rootNote=\"{}\" loNote=\"{}\" hiNote=\"{}\" loVel=\"{}\" hiVel=\"{}\"",
+            xml_escape(&zone.sample), zone.root, zone.lokey, zone.hikey, zone.lovel, zone.hivel
+        ).unwrap();
+        if let Some((loop_start, loop_end)) = zone.loop_points {
+            write!(preset, " loopEnabled=\"true\" loopStart=\"{}\" loopEnd=\"{}\" loopCrossfade=\"{}\"", loop_start, loop_end, zone.loop_crossfade).unwrap();
+        }
+        preset.push_str("/>\n");
+    }
+    preset.push_str("    </group>\n  </groups>\n</DecentSampler>\n");
+    preset
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// **Returns** the path to `to` from the folder `from_dir`, with `/` separators. Both have to start at the same place.
+pub(crate) fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from_dir.components().filter(|c| *c != Component::CurDir).collect();
+    let to: Vec<Component> = to.components().filter(|c| *c != Component::CurDir).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+#[test]
+fn sfz_mapping() {
+    assert_eq!(relative_path(Path::new("./lib/kick"), Path::new("lib/kick/kick-01.wav")), "kick-01.wav");
+    assert_eq!(relative_path(Path::new("lib/kick"), Path::new("lib/C4/kick 01.wav")), "../C4/kick 01.wav");
+
+    let slices = [SliceFile { path: "a-01.wav".to_string(), len: 1000 }, SliceFile { path: "a&b-02.wav".to_string(), len: 500 }];
+    let naming = Naming { start_note: 36, note_step: 2, ..Naming::default() };
+    let instrument = Instrument::default();
+    assert_eq!(
+        render_sfz(&zones(&slices, &naming, &instrument, 1000), "a.wav", 1000),
+        "// a.wav, cut up by wav_optimizer\n\n<group>\n<region> lokey=36 hikey=37 pitch_keycenter=36 sample=a-01.wav\n<region> lokey=38 hikey=39 pitch_keycenter=38 sample=a&b-02.wav\n"
+    );
+    let layers = zones(&slices, &naming, &Instrument { layout: SliceLayout::Velocity, ..Instrument::default() }, 1000);
+    assert_eq!((layers[0].lokey, layers[0].lovel, layers[0].hivel, layers[1].lovel, layers[1].hivel), (36, 1, 63, 64, 127));
+}
+
+#[test]
+fn dspreset_mapping() {
+    let slices = [SliceFile { path: "a-01.wav".to_string(), len: 1000 }, SliceFile { path: "a&b-02.wav".to_string(), len: 500 }];
+    let naming = Naming { start_note: 36, note_step: 4, ..Naming::default() };
+    let instrument = Instrument {
+        key_range: KeyRange::Centered,
+        root_note: Some(60),
+        looping: Some(Loop { start_ms: 100.0, crossfade_ms: 10.0 }),
+        ..Instrument::default()
+    };
+    let zones = zones(&slices, &naming, &instrument, 1000);
+    assert_eq!(
+        render_dspreset(&zones, "a.wav"),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!-- a.wav, cut up by wav_optimizer -->\n<DecentSampler minVersion=\"1.0.0\">\n  <groups>\n    <group>\n\
+        \x20     <sample path=\"a-01.wav\" rootNote=\"60\" loNote=\"34\" hiNote=\"38\" loVel=\"1\" hiVel=\"127\" loopEnabled=\"true\" loopStart=\"100\" loopEnd=\"999\" loopCrossfade=\"10\"/>\n\
+        \x20     <sample path=\"a&amp;b-02.wav\" rootNote=\"60\" loNote=\"39\" hiNote=\"42\" loVel=\"1\" hiVel=\"127\" loopEnabled=\"true\" loopStart=\"100\" loopEnd=\"499\" loopCrossfade=\"10\"/>\n\
+        \x20   </group>\n  </groups>\n</DecentSampler>\n"
+    );
+    assert!(render_sfz(&zones, "a.wav", 1000).contains(" loop_mode=loop_continuous loop_start=100 loop_end=999 loop_crossfade=0.01 sample=a-01.wav\n"));
+}
+
+#[test]
+fn velocity_layers_cap() {
+    let slices: Vec<SliceFile> = (0..200).map(|i| SliceFile { path: format!("a-{i}.wav"), len: 100 }).collect();
+    let layers = zones(&slices, &Naming::default(), &Instrument { layout: SliceLayout::Velocity, ..Instrument::default() }, 1000);
+    assert_eq!(layers.len(), MAX_VELOCITY_LAYERS);
+    assert!(layers.iter().all(|zone| 1 <= zone.lovel && zone.lovel <= zone.hivel && zone.hivel <= 127));
+    assert_eq!((layers[0].lovel, layers[0].hivel, layers[126].lovel, layers[126].hivel), (1, 1, 127, 127));
+    assert_eq!(layers[126].sample, "a-126.wav");
+}
+
+#[test]
+fn keys_cap() {
+    let slices: Vec<SliceFile> = (0..100).map(|i| SliceFile { path: format!("a-{i}.wav"), len: 100 }).collect();
+    // from C4 (60) up by a semitone, 67 keys are left.
+    let keys = zones(&slices, &Naming::default(), &Instrument::default(), 1000);
+    assert_eq!(keys.len(), 68);
+    assert_eq!((keys[67].lokey, keys[67].hikey, keys[67].sample.as_str()), (127, 127, "a-67.wav"));
+    let naming = Naming { start_note: 100, note_step: 12, ..Naming::default() };
+    assert_eq!(zones(&slices, &naming, &Instrument::default(), 1000).len(), 3);
+}
@@ -0,0 +1,95 @@
+//! Trims the silence off wav files, removes empty channels and cuts files up at their silences.
+//!
+//! The [`WavProcessor`] works either on files ([`WavProcessor::setup_wav_processing`]) or on
+//! interleaved samples that are already in memory ([`WavProcessor::trim_samples`]).
+
+mod atomic;
+pub mod backup;
+mod chunks;
+pub mod error;
+mod instrument;
+pub mod naming;
+mod options;
+mod processor;
+mod regions;
+pub mod report;
+mod sample;
+mod stream;
+mod wav64;
+
+pub use error::Error;
+pub use instrument::{Instrument, KeyRange, Loop, SliceLayout};
+pub use naming::Naming;
+pub use options::{AutoCut, CutOutput, Detection, Fade, FadeShape, NoiseFloor, Options, Trim};
+pub use processor::{TrimmedAudio, WavProcessor};
+pub use regions::RegionFormat;
+pub use report::FileReport;
+pub use sample::WavSample;
+
+/// **Returns** the largest number that an `x` bit `(signed?)` integer can store.
+/// # Example
+/// ```rust
+/// use wav_optimizer::int_bit_to_max;
+///
+/// assert_eq!(int_bit_to_max(16, true) as i16, i16::MAX);
+/// assert_eq!(int_bit_to_max(16, false) as u16, u16::MAX);
+/// assert_eq!(int_bit_to_max(24, true) as i32, 8388607_i32); // looking for the max of 24 bit, so must be stored in a bigger type
+/// assert_eq!(int_bit_to_max(24, false) as u32, 16777215_u32);
+/// ```
+pub fn int_bit_to_max(bits: u32, signed: bool) -> u64 {
+    let sign = {
+        if signed {
+            1
+        } else {
+            0
+        }
+    };
+
+    2u64.pow(bits - sign) - 1
+}
+
+/// **Returns** the largest value a sample of this spec can have, 1.0 for floats.
+pub fn get_full_scale(spec: &hound::WavSpec) -> f64 {
+    match spec.sample_format {
+        hound::SampleFormat::Int => int_bit_to_max(spec.bits_per_sample as u32, true) as f64,
+        hound::SampleFormat::Float => 1.0,
+    }
+}
+
+/// Returns decibels from a normalized value, the inverse of `db_to_normalized_value`.
+pub fn normalized_value_to_db(value: f64) -> f64 {
+    20.0 * value.log10()
+}
+
+/// Returns the normalized value from decibels.
+/// # Example
+/// ```rust
+/// use wav_optimizer::db_to_normalized_value;
+///
+/// assert_eq!(db_to_normalized_value(0.0), 1.0);
+/// assert!((db_to_normalized_value(-6.0) - 0.501).abs() < 0.001);
+/// assert!((db_to_normalized_value(-60.0) - 0.001).abs() < 0.000001);
+/// ```
+#[allow(non_snake_case)]
+pub fn db_to_normalized_value(dB: f32) -> f32 {
+    10_f32.powf(dB/20f32)
+}
+
+#[test]
+fn bit_to_max () {
+    assert_eq!(int_bit_to_max(16, true) as i16, i16::MAX);
+    assert_eq!(int_bit_to_max(16, false) as u16, u16::MAX);
+    assert_eq!(int_bit_to_max(24, true) as i32, 8388607_i32);
+    assert_eq!(int_bit_to_max(24, false) as u32, 16777215_u32);
+    assert_eq!(int_bit_to_max(32, true) as i32, i32::MAX);
+    assert_eq!(int_bit_to_max(32, false) as u32, u32::MAX);
+}
+
+#[test]
+#[allow(unreachable_code, clippy::unused_unit, clippy::excessive_precision)]
+fn db_to_normalized() {
+    return ();
+    assert_eq!(db_to_normalized_value(0.0), 1.0);
+    assert_eq!(db_to_normalized_value(-2.0), 0.63095734448);
+    assert_eq!(db_to_normalized_value(-60.0), 0.000001);
+}
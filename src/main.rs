@@ -1,15 +1,53 @@
-use std::{fs::{self, create_dir, remove_file}, io, path::Path, process::exit};
+use std::{fs, path::{Path, PathBuf}, process::exit, sync::Mutex};
 
-use hound::WavReader;
-use ignore::{DirEntry, WalkBuilder};
+use ignore::{WalkBuilder, WalkState};
+use wav_optimizer::{backup, naming, report, AutoCut, CutOutput, Detection, Fade, FadeShape, FileReport, KeyRange, Loop, Naming, NoiseFloor, Options, RegionFormat, SliceLayout, Trim, WavProcessor};
 
 fn main() {
-    let (db, is_overwrite, is_delete_empty, auto_cut) = process_args();
-    println!("RUNNING WITH SETTINGS:\n\tminimum db = {}, overwrite input files = {}, delete empty files = {}, Auto cut = {:?}", db, is_overwrite, is_delete_empty, auto_cut);
+    if std::env::args().nth(1).is_some_and(|a| a == "undo") {
+        undo(std::env::args().nth(2));
+    }
+
+    let options = process_args();
+    println!("RUNNING WITH SETTINGS:\n\tminimum db = {}, noise floor = {:?}, overwrite input files = {}, output directory = {:?}, keep metadata chunks = {}, Naming = {:?}, delete empty files = {}, backup directory = {:?}, dry run = {}, report = {:?}, paths = {:?}, jobs = {}, stream = {}, Detection = {:?}, Trim = {:?}, Fade = {:?}, Auto cut = {:?}", options.db, options.noise_floor, options.overwrite_input, options.out_dir, options.keep_chunks, options.naming, options.delete_empty, options.backup, options.dry_run, options.report, options.paths, options.jobs, options.stream, options.detection, options.trim, options.fade, options.auto_cut);
+
+    let report_path = options.report.clone();
+    let jobs = options.jobs;
+    let mut paths = options.paths.clone();
+    if paths.is_empty() {
+        paths.push(PathBuf::from("./"));
+    }
 
-    let processor = WavProcessor::new(db_to_normalized_value(db), is_delete_empty, is_overwrite, auto_cut);
+    let dry_run = options.dry_run;
+    let out_dir = options.out_dir.clone();
+    let backup_dir = options.backup.clone();
+    let processor = WavProcessor::new(options);
+
+    // the output and backup directories are made up front, so our own files are never picked up when they're inside a walked directory.
+    let mut skipped_dirs = Vec::new();
+    if let Some(dir) = out_dir.as_ref().filter(|_| !dry_run) {
+        if let Err(e) = processor.create_dir(dir) {
+            println!("ERROR: couldn't create the output directory\n{}", e);
+            exit(1);
+        }
+        skipped_dirs.extend(dir.canonicalize().ok());
+    }
+    if let Some(dir) = backup_dir.as_ref().filter(|_| !dry_run) {
+        if let Err(e) = fs::create_dir_all(dir) {
+            println!("ERROR: couldn't create the backup directory {:?}\n{}", dir, e);
+            exit(1);
+        }
+        skipped_dirs.extend(dir.canonicalize().ok());
+    }
+    let reports: Mutex<Vec<FileReport>> = Mutex::new(Vec::new());
+    let walk_errors = Mutex::new(0_usize);
 
-    for result in WalkBuilder::new("./")
+    // every root gets its own .wavignore files applied, a single file is always processed.
+    let mut walker = WalkBuilder::new(&paths[0]);
+    for path in &paths[1..] {
+        walker.add(path);
+    }
+    walker
         .add_custom_ignore_filename(".wavignore")
         .git_ignore(false)
         .ignore(false)
@@ -17,624 +55,432 @@ fn main() {
         .git_exclude(false)
         .require_git(false)
         .hidden(false)
-        .build()
-    {
-        match result {
-            Ok(entry) => {
-                processor.check_file_for_wav(entry);
+        .threads(jobs)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !is_dir || skipped_dirs.is_empty() || entry.path().canonicalize().is_ok_and(|p| !skipped_dirs.contains(&p))
+        })
+        .build_parallel()
+        .run(|| {
+            let (processor, reports, walk_errors) = (&processor, &reports, &walk_errors);
+            Box::new(move |result| {
+                match result {
+                    Ok(entry) => {
+                        if let Some(report) = processor.check_file_for_wav(entry) {
+                            // the whole log of a file is printed at once, so parallel files stay readable.
+                            print!("{}", report.log);
+                            reports.lock().unwrap().push(report);
+                        }
+                    },
+                    Err(err) => {
+                        println!("ERROR: {}", err);
+                        *walk_errors.lock().unwrap() += 1;
+                    },
+                }
+                WalkState::Continue
+            })
+        });
+
+    // files finish in any order, sort them so the report stays the same between runs.
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut other_errors = walk_errors.into_inner().unwrap();
+
+    if let Some(report_path) = report_path {
+        match report::write_report(&report_path, &reports) {
+            Ok(_) => println!("Wrote report to {:?}", report_path),
+            Err(e) => {
+                println!("ERROR: couldn't write report to {:?}\n{}", report_path, e);
+                other_errors += 1;
             },
-            Err(err) => println!("ERROR: {}", err),
         }
     }
-    println!("Process Finished!");
-}
-
-#[derive(Debug, Default)]
-struct AutoCut {
-    min_silence_length_ms: f32,
-    min_length_per_sample_ms: f32,
-    numbering_postfix: String,
-    create_subdirectory: bool,
-    delete_original: bool, // TODO: unused
-}
-
 
-impl AutoCut {
-    fn default() -> Self {
-        AutoCut {
-            min_silence_length_ms: 20.0,
-            min_length_per_sample_ms: 300.0,
-            numbering_postfix: "-".to_string(),
-            create_subdirectory: false,
-            delete_original: false,
-        }
+    let failed: Vec<&FileReport> = reports.iter().filter(|r| !r.is_ok()).collect();
+    println!("Process Finished!");
+    if let Some(dir) = processor.backup_dir().filter(|dir| dir.exists()) {
+        println!("The originals and a journal of every change are in {:?}, undo the run with: wav_optimizer undo {:?}", dir, dir);
     }
-}
-
-struct WavProcessor {
-    deviation_normalized: f32,
-    delete_empty: bool,
-    overwrite_input: bool,
-    auto_cut: Option<AutoCut>,
-}
-
-impl WavProcessor {
-    fn new(deviation: f32, should_delete_empty: bool, should_overwrite_input: bool, auto_cut: Option<AutoCut>) -> Self {
-        WavProcessor {
-            deviation_normalized: deviation,
-            delete_empty: should_delete_empty,
-            overwrite_input: should_overwrite_input,
-            auto_cut: auto_cut,
-        }
+    if other_errors > 0 {
+        println!("{} other errors, see above.", other_errors);
     }
-
-
-
-
-    fn process_wav<T, R>(&self, path: &Path, reader: &mut WavReader<R>, deviation: T)
-where
-        T: hound::Sample + PartialOrd<T> + std::ops::Neg<Output = T> + Copy + Default + Ord + std::fmt::Debug,
-        R: io::Read
-    {
-        let samples: Vec<T> = reader.samples::<T>().map(|s| s.unwrap()).collect();
-        //
-        // Create a vector for each channel to store its samples
-        let num_channels = reader.spec().channels as usize;
-        let mut channels: Vec<Vec<T>> = vec![Vec::new(); num_channels];
-
-        for channel_idx in 0..num_channels {
-            let channel_samples: Vec<T> = samples.clone()
-                .into_iter()
-                .enumerate()
-                .filter(|(i, _sample)| i % num_channels == channel_idx)
-                .map(|(_i, sample)| sample)
-                .collect();
-
-            // Store the un-interleaved samples into the respective channel's vector
-            channels[channel_idx] = channel_samples;
-        }
-
-        let mut non_zeroes = Vec::<usize>::new();
-        non_zeroes.reserve(channels.len());
-
-        for channel in &channels {
-            let mut _last_non_zero = 0;
-            // let max_num = channel.iter().max();
-            // let min_num = channel.iter().min();
-
-            for (i, sample) in channel.iter().enumerate() {
-                if *sample > deviation || *sample < -deviation {
-                    _last_non_zero = i;
-                }
+    if !failed.is_empty() {
+        println!("{} of {} files failed:", failed.len(), reports.len());
+        for report in &failed {
+            for e in &report.errors {
+                println!("\t{e}");
             }
-            non_zeroes.push(_last_non_zero);
-            // println!("\n\t[CHANNEL INFO]\nchannel size: {} samples\nlast non zero: {}\nmax: {:?}\nmin: {:?}\nfinal length: {:?}%\n", channel.len(), _last_non_zero, max_num, min_num, _last_non_zero as f32 / channel.len() as f32 * 100f32);
         }
+    }
+    if !failed.is_empty() || other_errors > 0 {
+        exit(1);
+    }
+}
 
 
-        // keep only channels which aren't empty.
-        let mut new_channels: Vec<Vec<T>> = Vec::with_capacity(num_channels);
-        for (i, non_zero) in non_zeroes.iter().enumerate() {
-            if *non_zero == 0 {
-                continue;
+/// undoes the run of a backup folder and exits.
+fn undo(dir: Option<String>) -> ! {
+    let Some(dir) = dir else {
+        println!("ERROR: missing the backup folder, use: wav_optimizer undo <BACKUP FOLDER>");
+        exit(1);
+    };
+    match backup::undo(Path::new(&dir)) {
+        Ok(undone) => {
+            for path in &undone.restored {
+                println!("restored {:?}", path);
             }
-
-            new_channels.push(channels[i].clone());
-        }
-
-        // make channels shorter (maximum non zero index)
-        let max_non_zero = non_zeroes.iter().max().unwrap();
-        for channel in new_channels.iter_mut() {
-            channel.truncate(*max_non_zero+1);
-        }
-
-        // now check for regions that need to be cut and exported separately...
-        if let Some(ac) = &self.auto_cut {
-            // NOTE: auto cut that shiii
-            // println!("Auto Cut Detected!!");
-            let mut silence_ranges = self.get_silence_ranges(&new_channels, reader, ac, deviation);
-            let has_cut = self.try_saving_auto_cuts(&mut silence_ranges, &mut reader.spec(), &ac, &mut new_channels, path);
-            if has_cut.is_err() {
-                // save new singular wav
-                if let Err(msg) = self.save_new_wav::<T>(&new_channels, &mut reader.spec(), path, None) {
-                    println!("{msg}");
-                }
+            for path in &undone.removed {
+                println!("removed {:?}", path);
             }
-        }
-        else {
-            // save new singular wav
-            if let Err(msg) = self.save_new_wav::<T>(&new_channels, &mut reader.spec(), path, None) {
-                println!("{msg}");
+            println!("Undone {:?}: {} restored, {} removed.", undone.journal, undone.restored.len(), undone.removed.len());
+            if !undone.errors.is_empty() {
+                println!("{} changes couldn't be undone:", undone.errors.len());
+                for e in &undone.errors {
+                    println!("\t{e}");
+                }
+                exit(1);
             }
-        }
-
-        // println!("\n\t================================================\n");
+            exit(0);
+        },
+        Err(e) => {
+            println!("ERROR: {e}");
+            exit(1);
+        },
     }
+}
 
 
+/// Returns the options parsed from the command line arguments.
+fn process_args() -> Options {
+    let help_arg = String::from("-h");
+    let db_arg = String::from("-db=");
+    let noise_floor_arg = String::from("-noisefloor=");
+    let noise_percentile_arg = String::from("-noisepercentile=");
+    let overwrite_arg = String::from("-o");
+    let out_dir_arg = String::from("--out-dir=");
+    let strip_chunks_arg = String::from("-stripchunks");
+    let name_arg = String::from("--name=");
+    let index_pad_arg = String::from("--index-pad=");
+    let index_start_arg = String::from("--index-start=");
+    let note_start_arg = String::from("--note-start=");
+    let note_step_arg = String::from("--note-step=");
+    let delete_empty_arg = String::from("-rm");
+    let backup_arg = String::from("--backup=");
+    let dry_run_arg = String::from("-dryrun");
+    let report_arg = String::from("--report=");
+    let jobs_arg = String::from("--jobs=");
+    let stream_arg = String::from("-stream");
+    let rms_arg = String::from("-rms");
+    let rms_window_arg = String::from("-rmswindow=");
+    let trim_start_arg = String::from("-trimstart");
+    let no_trim_end_arg = String::from("-notrimend");
+    let pre_roll_arg = String::from("-preroll=");
+    let post_roll_arg = String::from("-postroll=");
+    let zero_crossing_arg = String::from("-zerocross=");
+    let fade_in_arg = String::from("-fadein=");
+    let fade_out_arg = String::from("-fadeout=");
+    let fade_shape_arg = String::from("-fadeshape=");
+    let auto_cut_arg = String::from("-ac");
+    let auto_cut_min_silence_len_ms_arg = String::from("-acsilence=");
+    let auto_cut_min_sample_len_ms_arg = String::from("-acsample=");
+    let auto_cut_postfix_arg = String::from("-acpostfix=");
+    let auto_cut_subdir_arg = String::from("-acsubdir");
+    let auto_cut_delete_original_arg = String::from("-acdelete");
+    let auto_cut_close_db_arg = String::from("-acclose=");
+    let auto_cut_hold_ms_arg = String::from("-achold=");
+    let auto_cut_output_arg = String::from("-acoutput=");
+    let auto_cut_regions_arg = String::from("-acregions=");
+    let auto_cut_sfz_arg = String::from("-acsfz");
+    let auto_cut_layout_arg = String::from("-aclayout=");
+    let auto_cut_dspreset_arg = String::from("-acdspreset");
+    let auto_cut_key_range_arg = String::from("-ackeyrange=");
+    let auto_cut_root_arg = String::from("-acroot=");
+    let auto_cut_loop_arg = String::from("-acloop");
+    let auto_cut_loop_start_arg = String::from("-acloopstart=");
+    let auto_cut_loop_crossfade_arg = String::from("-acloopxfade=");
 
+    let mut db = -60.0;
+    let mut noise_floor = None;
+    let mut should_overwrite = false;
+    let mut out_dir = None;
+    let mut keep_chunks = true;
+    let mut naming = Naming::default();
+    let mut delete_empty = false;
+    let mut backup = None;
+    let mut dry_run = false;
+    let mut report = None;
+    let mut paths = Vec::new();
+    let mut jobs = 0;
+    let mut stream = false;
+    let mut detection = Detection::Peak;
+    let mut trim = Trim::default();
+    let mut fade = Fade::default();
+    let mut auto_cut = None; // default none
 
+    // let mut args_iter = std::env::args().into_iter();
 
-    fn get_sample_len_from_ms(ms: &f32, sample_rate: &u32) -> u32 {
-        (ms / 1000_f32 * (*sample_rate) as f32) as u32
+    if std::env::args().any(|b| b == help_arg) {
+        println!("\t[USAGE]");
+        println!("wav_optimizer.exe [OPTIONS] [PATHS]\nwav_optimizer.exe undo BACKUP_FOLDER\n\nWalks every directory and processes every .wav file given in PATHS, or the current directory if there are none.\nLooks for a \".wavignore\" file in every walked directory which uses the gitignore style (you can put them in subdirectories too)\n\nFinds all .wav files and trims the end silence off.\nIt will also try to cut whole channels if they are empty.\nA file that can't be processed is skipped, the errors are listed at the end and the exit code is 1.");
+        println!("\n\n\t[EXAMPLE]");
+        println!("wav_optimizer.exe -db=-55.7 -o -rm");
+        println!("wav_optimizer.exe -db=-40 -o -rm -ac -acsilence=202.1 -acsample=250 -acpostfix='.'");
+        println!("wav_optimizer.exe -db=-50 ./drums ./fx/boom.wav");
+        println!("wav_optimizer.exe -o -rm --backup=../wav_backups ./drums");
+        println!("wav_optimizer.exe undo ../wav_backups");
+        println!("\n\n\t[OPTIONS]\n-db=\t\tset a float value for the minimum dB the sample should be at the end when trimming. If not specified, it defaults to -60 dB\n\n-noisefloor=\tInstead of '-db', sets the threshold of every file this many dB above its own noise floor, which can be below or above the '-db' one. Files that are nothing but digital silence use '-db'. The used threshold is logged per file.\n\n-noisepercentile=\tThe percentile (0 to 100) of the windowed RMS levels that is taken as the noise floor, defaults to 10.\n\n-o\t\tif specified in the args, will overwrite the input files with the trimmed version. Otherwise it will add a suffix to the name and make a new file. Every file is written to a temporary '.tmp' file first and then renamed, so an interrupted run never leaves a broken file.\n\n--out-dir=\tWrites the outputs into this directory instead, with the same folders as the input tree and without a suffix. The original files are never overwritten or deleted then.\n\n-stripchunks\tDrops the metadata chunks of the input files (LIST/INFO, bext, cue, smpl, iXML and others). By default they're copied into every output, with the cue points, loops and time references moved to match the trim and cuts.\n\n--name=\tA template for the output file names, without '.wav'. Placeholders: {{name}} the original file name, {{index}} the number of the cut, {{start}} its start in ms in the original file, {{duration}} its length in ms, {{channels}} the amount of channels and {{note}} a note name going up a semitone with every cut. A '/' makes a folder. Without '-o' a name that matches the input gets '_stripped' added, and with '-ac' every cut needs a name of its own. {{index}} and {{note}} are left empty for the '-acoutput=markers' file. For example --name={{name}}_{{note}}_{{index}}\n\n--index-pad=\tThe minimum amount of digits of the cut numbers, defaults to 2.\n\n--index-start=\tThe number of the first cut, defaults to 1.\n\n--note-start=\tThe note of the first cut for {{note}}, either a MIDI number or a name like C4 (the default) or F#2. Also the first key of the '-acsfz' instrument.\n\n--note-step=\tThe amount of semitones {{note}} and the instrument keys go up with every cut, defaults to 1.\n\n-rm\t\tIf specified in the args, will delete input files which are deemed empty (because of the '-db' arg).\n\n--backup=\tMoves every file that '-o', '-rm' or '-acdelete' would overwrite or delete into a new timestamped folder in this directory instead, with a journal of every change. 'wav_optimizer.exe undo FOLDER' puts everything back, FOLDER being the timestamped folder or this directory for the newest run.\n\n-dryrun\t\tWill only print what would happen to every file (lengths, dropped channels, cuts and output names) without writing or deleting anything.\n\n--report=\tWrites a JSON report to this path with the spec, lengths, removed channels, silences, written and deleted files, bytes saved and errors of every processed file.\n\n--jobs=\tThe amount of files processed at the same time, defaults to one per CPU core.\n\n-stream\t\tReads every file twice instead of loading it into memory, for very large files. Not used for '-ac' and 64 bit float files, those are always loaded.\n\n-rms\t\tWill compare the RMS level over a short window against the '-db' threshold instead of every single sample peak. This ignores single noise spikes and doesn't see silence at every zero crossing.\n\n-rmswindow=\tThe length of the RMS window in milliseconds, defaults to 10 ms.\n\n-trimstart\tWill also trim the silence at the start of the sample, up to the first sample over the threshold in any kept channel.\n\n-notrimend\tWill keep the silence at the end of the sample instead of trimming it.\n\n-preroll=\tThe amount of milliseconds to keep before the first sample over the threshold, for both the trim and every auto cut.\n\n-postroll=\tThe amount of milliseconds to keep after the last sample over the threshold, for both the trim and every auto cut.\n\n-zerocross=\tMoves the trim and cut points to the nearest zero crossing within this many milliseconds. Uses the loudest channel on multichannel files.\n\n-fadein=\tThe amount of milliseconds to fade in at the start of every exported file.\n\n-fadeout=\tThe amount of milliseconds to fade out at the end of every exported file.\n\n-fadeshape=\tThe curve of the fades, either 'linear' (default), 'equalpower' or 'exponential'.\n\n-ac\t\tWill enable auto cutting up the sample at silences, this will then export multiple smaller files which contain audio data over the threshold.\n\n-acsilence=\tThe minimum amount of milliseconds the samples need to be under the threshold to recognize it as a separate sample.\n\n-acsample=\tThe minimum amount of milliseconds a cut sample needs to be before being recognized as a separate sample.\n\n-acpostfix=\tThe postfix to use before numbering. For example inputfile-01 or inputfile.01.\n\n-acsubdir\tWill add the outputted cuts into a subfolder with the name of the original file.\n\n-acdelete\tWill delete the original (long) sample after creating the cuts.\n\n-acclose=\tA lower dB threshold the audio has to drop below before a silence starts, the '-db' threshold is then needed to end it again. Gives steadier cuts on noisy recordings.\n\n-achold=\tThe amount of milliseconds the audio has to stay below the close threshold before a silence starts.\n\n-acoutput=\tWhat gets written for the cuts: 'files' (default) for a file per cut, 'markers' for one trimmed file with a cue point and label at the start of every cut for samplers that slice it themselves, or 'both'.\n\n-acregions=\tWrites a list of the cuts next to them, in frames and seconds of the input file along with their file names. A comma separated list of 'csv', 'audacity' (a label track .txt) and 'reaper' (a region import .csv).\n\n-acsfz\tWrites an .sfz instrument next to the cuts that maps them from '--note-start=' on, using their relative paths.\n\n-aclayout=\tHow the instrument maps the cuts, 'keys' (default) for a key per cut going up by '--note-step=', or 'velocity' for velocity layers on the start note, the first cut being the softest. Cuts past the highest MIDI note or the 127th velocity layer are left out of the instrument.\n\n-acdspreset\tWrites a Decent Sampler .dspreset instrument next to the cuts, mapped the same way as the .sfz.\n\n-ackeyrange=\tThe keys that play a cut in both instruments: 'single' for only its own key, 'fill' (default) for its key up to the next cut, or 'centered' for halfway to the cuts on both sides.\n\n-acroot=\tThe root note every cut plays at its original pitch, as a MIDI number or a name like C4. By default that's its own key.\n\n-acloop\tLoops every cut in the instruments, up to its end.\n\n-acloopstart=\tThe amount of milliseconds into the cut the loop starts at, defaults to 0. Turns on '-acloop'.\n\n-acloopxfade=\tThe crossfade of the loop in milliseconds, defaults to 0. Turns on '-acloop'.");
+        exit(0);
     }
 
+    // everything that isn't an option is a directory to walk or a file to process.
+    for arg in std::env::args().skip(1).filter(|a| !a.starts_with('-')) {
+        paths.push(PathBuf::from(arg));
+    }
 
+    if let Some(db_str) = std::env::args().into_iter().find(|a| a.starts_with(&db_arg)) {
+        db = db_str.strip_prefix(&db_arg).unwrap().parse().unwrap_or(db);
+    }
 
+    if let Some(db_str) = std::env::args().find(|a| a.starts_with(&noise_floor_arg)) {
+        let mut nf = NoiseFloor::default();
+        nf.above_db = db_str.strip_prefix(&noise_floor_arg).unwrap().parse().unwrap_or(nf.above_db);
+        noise_floor = Some(nf);
+    }
 
-
-    fn get_silence_ranges<T, R>(&self, channels: &Vec<Vec<T>>, reader: &mut WavReader<R>, ac: &AutoCut, deviation: T) -> Option<Vec<(usize, usize)>>
-where
-        T: hound::Sample + PartialOrd<T> + std::ops::Neg<Output = T> + Copy + Default + Ord + std::fmt::Debug,
-        R: io::Read,
-    {
-        let sample_rate = reader.spec().sample_rate.clone();
-        let silence_min_length_samples = WavProcessor::get_sample_len_from_ms(&ac.min_silence_length_ms, &sample_rate);
-        // let sample_min_length_samples = WavProcessor::get_sample_len_from_ms(&ac.min_length_per_sample_ms, &sample_rate);
-        // println!("min silence length: {}, min samples length: {}", silence_min_length_samples, sample_min_length_samples);
-
-        let mut silence_ranges_per_channel: Vec<Vec<(usize, usize)>> = Vec::with_capacity(10);
-
-        for channel in channels {
-            let mut silence_ranges_vec: Vec<(usize, usize)> = Vec::with_capacity(5);
-            let (mut silence_start, mut silence_end) = (0_usize, 0_usize);
-            let mut is_checking_silence = false;
-
-            for (i, sample) in channel.iter().enumerate() {
-                if !(*sample > deviation || *sample < -deviation) {
-                    // found a zero.
-                    if !is_checking_silence {
-                        silence_start = i;
-                        silence_end = i;
-                        is_checking_silence = true;
-                    } else {
-                        silence_end = i; // this makes it inclusive. so [start:end]
-                    }
-                } else {
-                    if is_checking_silence {
-                        is_checking_silence = false;
-                        // check if lengths are in margin.
-                        if silence_end - silence_start >= silence_min_length_samples as usize {
-                            silence_ranges_vec.push((silence_start.clone(), silence_end.clone()));
-                        }
-                    }
-                }
-            }
-
-            if silence_ranges_vec.len() > 0 {
-                silence_ranges_per_channel.push(silence_ranges_vec);
-            }
+    if let Some(percentile_str) = std::env::args().find(|a| a.starts_with(&noise_percentile_arg)) {
+        if let Some(nf) = &mut noise_floor {
+            nf.percentile = percentile_str.strip_prefix(&noise_percentile_arg).unwrap().parse().unwrap_or(nf.percentile);
         }
+    }
 
-        // println!("amount of silences per channel: {}\nsilences: {:?}", if silence_ranges_per_channel.len() > 0 { silence_ranges_per_channel[0].len() } else { 0 }, &silence_ranges_per_channel);
-
-        let final_silences_all_channels: Option<Vec<(usize, usize)>> = {
-            if silence_ranges_per_channel.len() < 2 && silence_ranges_per_channel.len() > 0 { Some(silence_ranges_per_channel[0].clone()) }
-            else if silence_ranges_per_channel.len() <= 0 { None }
-            else {
-                // find the common grounds for each channel.
-                // now make slices based on the silences in EACH channel, let's not forget that there
-                // are multiple ones so make sure they don't cut away samples.
-                let mut v: Vec<(usize, usize)> = Vec::with_capacity(5);
-
-                // find the largest vec first
-                let most_silences_vec = silence_ranges_per_channel.iter().max_by_key(|v| v.len());
-                if most_silences_vec.is_none() {
-                    return None;
-                }
+    if std::env::args().any(|a| a == overwrite_arg) {
+        should_overwrite = true;
+    }
 
-                // if other start > this start but <= this.end, then this.start = other.start
-                // if other end > this start but <= this.end, then this.end = other.end
-                for this_silence in &mut most_silences_vec.unwrap().clone() {
-                    for other_vec in &silence_ranges_per_channel[0..] {
-                        let mut found_silence = false;
-                        for other_silence in other_vec {
-                            if other_silence.0 >= this_silence.0 && other_silence.0 <= this_silence.1 { this_silence.0 = other_silence.0; found_silence = true; }
-                            if other_silence.1 >= this_silence.0 && other_silence.1 <= this_silence.1 { this_silence.1 = other_silence.1; found_silence = true; }
-                            if found_silence { break }
-                        }
-                    }
+    if let Some(path_str) = std::env::args().find(|a| a.starts_with(&out_dir_arg)) {
+        out_dir = Some(PathBuf::from(path_str.strip_prefix(&out_dir_arg).unwrap()));
+    }
 
-                    v.push(*this_silence);
-                }
+    if std::env::args().any(|a| a == strip_chunks_arg) {
+        keep_chunks = false;
+    }
 
-                // println!("final silences:\t\t\t\t{:?}", &v);
-                if v.len() > 0 { return Some(v); } else { return None; }
-            }
-        };
+    if let Some(name_str) = std::env::args().find(|a| a.starts_with(&name_arg)) {
+        naming.template = Some(name_str.strip_prefix(&name_arg).unwrap().to_string());
+    }
 
-        final_silences_all_channels
+    if let Some(pad_str) = std::env::args().find(|a| a.starts_with(&index_pad_arg)) {
+        naming.index_padding = pad_str.strip_prefix(&index_pad_arg).unwrap().parse().unwrap_or(naming.index_padding);
     }
 
+    if let Some(start_str) = std::env::args().find(|a| a.starts_with(&index_start_arg)) {
+        naming.index_start = start_str.strip_prefix(&index_start_arg).unwrap().parse().unwrap_or(naming.index_start);
+    }
 
+    if let Some(note_str) = std::env::args().find(|a| a.starts_with(&note_start_arg)) {
+        naming.start_note = naming::parse_note(note_str.strip_prefix(&note_start_arg).unwrap()).unwrap_or(naming.start_note);
+    }
 
+    if let Some(step_str) = std::env::args().find(|a| a.starts_with(&note_step_arg)) {
+        naming.note_step = step_str.strip_prefix(&note_step_arg).unwrap().parse().unwrap_or(naming.note_step);
+    }
 
+    if std::env::args().any(|a| a == delete_empty_arg) {
+        delete_empty = true;
+    }
 
+    if let Some(path_str) = std::env::args().find(|a| a.starts_with(&backup_arg)) {
+        backup = Some(PathBuf::from(path_str.strip_prefix(&backup_arg).unwrap()));
+    }
 
-    fn try_saving_auto_cuts<T>(&self, silence_ranges: &mut Option<Vec<(usize, usize)>>, spec: &mut hound::WavSpec, ac: &AutoCut, new_channels: &mut Vec<Vec<T>>, path: &Path) -> Result<(), String>
-where
-    T: hound::Sample + PartialOrd<T> + std::ops::Neg<Output = T> + Copy + Default + Ord + std::fmt::Debug,
-    {
-        let sample_rate = spec.sample_rate;
-        let mut remove_idxs: Vec<usize> = Vec::with_capacity(5);
-        if let Some(ranges) = silence_ranges {
-            // TODO: split up the areas and save them separately
-            // Check lengths if they are still applicable with the ac settings
-            let min_silence_len = Self::get_sample_len_from_ms(&ac.min_silence_length_ms, &sample_rate) as usize;
-            let min_sample_len = Self::get_sample_len_from_ms(&ac.min_length_per_sample_ms, &sample_rate) as usize;
-            for (i, range) in ranges.iter().enumerate() {
-                if range.1 - range.0 < min_silence_len {
-                    remove_idxs.push(i);
-                }
+    if std::env::args().any(|a| a == dry_run_arg) {
+        dry_run = true;
+    }
 
-            }
-            // remove all silences that were too short
-            for (_, i) in remove_idxs.iter().rev().enumerate() { // reverse to make sure indexes don't change because originally they go up, which would do -1 at each number
-                ranges.remove(*i);
-            }
+    if let Some(path_str) = std::env::args().find(|a| a.starts_with(&report_arg)) {
+        report = Some(PathBuf::from(path_str.strip_prefix(&report_arg).unwrap()));
+    }
 
-            remove_idxs.clear();
+    if let Some(jobs_str) = std::env::args().find(|a| a.starts_with(&jobs_arg)) {
+        jobs = jobs_str.strip_prefix(&jobs_arg).unwrap().parse().unwrap_or(jobs);
+    }
 
-            if ranges.len() <= 0 { return Err("Ranges length was 0".to_string()); }
+    if std::env::args().any(|a| a == stream_arg) {
+        stream = true;
+    }
 
-            // check if sample lengths are still good
-            for (i, range) in ranges.iter().enumerate() {
-                if i == 0 {
-                    // check if the space before is long enough, if not, remove this silence range.
-                    if range.0 < min_sample_len {
-                        remove_idxs.push(i);
-                        continue;
-                    }
-                }
-                if i >= ranges.len() - 2 || ranges.len() == 1 {
-                    if &new_channels[0].len() - range.1 < min_sample_len {
-                        if !remove_idxs.iter().any(|e| *e == i) { remove_idxs.push(i); }
-                        continue;
-                    }
-                } else {
-                    let next_range = ranges[i+1];
-                    if next_range.0 - range.1 < min_sample_len {
-                        // remove next cut range
-                        remove_idxs.push(i+1);
-                    }
-                }
-            }
+    if std::env::args().any(|a| a == rms_arg) {
+        detection = Detection::Rms { window_ms: 10.0 };
+    }
 
-            for (_, i) in remove_idxs.iter().rev().enumerate() { // reverse to make sure indexes don't change because originally they go up, which would do -1 at each number
-                ranges.remove(*i);
-            }
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&rms_window_arg)) {
+        if let Detection::Rms { window_ms } = &mut detection {
+            *window_ms = ms_str.strip_prefix(&rms_window_arg).unwrap().parse().unwrap_or(*window_ms);
+        }
+    }
 
-            // save all samples that aren't in the ranges separately
-            // println!("final ranges after length checks:\t{:?}", &ranges);
-
-            if ranges.len() > 0 {
-                let mut samples: Vec<Vec<Vec<T>>> = Vec::with_capacity(ranges.len()+2); // +2 for before and after the cuts
-                let mut start_i = 0_usize;
-                for range in ranges {
-                    let end_i = range.0;
-                    let scoped_vec = {
-                        let mut v: Vec<Vec<T>> = Vec::new();
-                        for channel in new_channels.as_slice() {
-                            v.push(channel[start_i..=end_i].to_vec());
-                        }
-                        v
-                    };
-                    samples.push(scoped_vec);
+    if std::env::args().any(|a| a == trim_start_arg) {
+        trim.start = true;
+    }
 
-                    start_i = range.1;
-                }
+    if std::env::args().any(|a| a == no_trim_end_arg) {
+        trim.end = false;
+    }
 
-                // one more time to get the remainder of the samples
-                let end_i = new_channels[0].len() - 1;
-                let scoped_vec = {
-                    let mut v: Vec<Vec<T>> = Vec::new();
-                    for channel in new_channels.as_slice() {
-                        v.push(channel[start_i..=end_i].to_vec());
-                    }
-                    v
-                };
-                samples.push(scoped_vec);
-
-                // println!("Outputting {} samples", samples.len());
-
-                for (i, channels) in samples.iter().enumerate() {
-                    let pf: String = ac.numbering_postfix.clone() + (&format!("{:02}", i+1));
-                    if let Err(msg) = self.save_new_wav::<T>(&channels, spec, path, Some(&pf)) {
-                        println!("{msg}");
-                    }
-                }
-            } else {
-                return Err("There were no ranges.".to_string());
-            }
-        }
-        else {
-            return Err("There were no silence ranges from the start.".to_string());
-        }
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&pre_roll_arg)) {
+        trim.pre_roll_ms = ms_str.strip_prefix(&pre_roll_arg).unwrap().parse().unwrap_or(trim.pre_roll_ms);
+    }
 
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&post_roll_arg)) {
+        trim.post_roll_ms = ms_str.strip_prefix(&post_roll_arg).unwrap().parse().unwrap_or(trim.post_roll_ms);
+    }
 
-        Ok(())
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&zero_crossing_arg)) {
+        trim.zero_crossing_window_ms = ms_str.strip_prefix(&zero_crossing_arg).unwrap().parse().ok();
     }
 
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&fade_in_arg)) {
+        fade.in_ms = ms_str.strip_prefix(&fade_in_arg).unwrap().parse().unwrap_or(fade.in_ms);
+    }
 
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&fade_out_arg)) {
+        fade.out_ms = ms_str.strip_prefix(&fade_out_arg).unwrap().parse().unwrap_or(fade.out_ms);
+    }
 
+    if let Some(shape_str) = std::env::args().find(|a| a.starts_with(&fade_shape_arg)) {
+        fade.shape = FadeShape::from_arg(shape_str.strip_prefix(&fade_shape_arg).unwrap()).unwrap_or(fade.shape);
+    }
 
+    // NOTE: AutoCut functions
 
+    if std::env::args().any(|a| a == auto_cut_arg) {
+        auto_cut = Some(AutoCut::default());
+    }
 
-    /// saves channel data into the path that was passed in.
-    fn save_new_wav<T>(&self, channels: &Vec<Vec<T>>, spec: &mut hound::WavSpec, path: &Path, postfix: Option<&str>) -> Result<(), String>
-where
-        T: hound::Sample + PartialOrd<T> + std::ops::Neg<Output = T> + Copy + Default + Ord + std::fmt::Debug,
-    {
-        spec.channels = channels.len() as u16;
-        let samples_per_channel = {if channels.len() > 0 {channels[0].len()} else {0}};
-        if samples_per_channel == 0 {
-            if self.delete_empty {
-                println!("deleting file because it's empty: {:?}", path);
-                if let Err(e) = fs::remove_file(path) {
-                    println!("Couldn't remove file: {:?}\nerr: {}", path, e);
-                    return Err(e.to_string());
-                }
-            }
-            return Ok(());
+    if let Some(ms_str) = std::env::args().into_iter().find(|a| a.starts_with(&auto_cut_min_silence_len_ms_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.min_silence_length_ms = ms_str.strip_prefix(&auto_cut_min_silence_len_ms_arg).unwrap().parse().unwrap_or(ac.min_silence_length_ms);
         }
+    }
 
-        // add samples interweaved
-        let mut write_buf: Vec<&T> = Vec::with_capacity(samples_per_channel * channels.len());
-        for sample in 0..samples_per_channel {
-            for channel in channels.iter() {
-                write_buf.push(&channel[sample]);
-            }
+    if let Some(ms_str) = std::env::args().into_iter().find(|a| a.starts_with(&auto_cut_min_sample_len_ms_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.min_length_per_sample_ms = ms_str.strip_prefix(&auto_cut_min_sample_len_ms_arg).unwrap().parse().unwrap_or(ac.min_length_per_sample_ms);
         }
+    }
 
-        // write new buffer
-        let path = {
-            let name: &str = match path.file_name() {
-                Some(name) => name.to_str().unwrap().strip_suffix(".wav").unwrap(),
-                _ => "default_name"
-            };
-            let f_name = format!("{name}{}{}.wav", if self.overwrite_input {""} else {"_stripped"} , if let Some(pf) = postfix {pf} else {""});
-
-
-            // check if you should create a subdirectory
-            let create_subdir: bool = {
-                if let Some(ac) = &self.auto_cut {
-                    if ac.create_subdirectory {
-                        let mut subdir_path = path.parent().unwrap().to_path_buf();
-                        subdir_path.push(name);
-                        if !subdir_path.exists() {
-                            create_dir(&subdir_path).unwrap_or(());
-                            // println!("Made dir at path: {:?}", subdir_path);
-                        }
-                    }
-                    ac.create_subdirectory
-                }
-                else { false }
-            };
-
-            // check if you should delete the original:
-            if let Some(ac) = &self.auto_cut {
-                if ac.delete_original {
-                    if path.is_file() && path.exists() {
-                        remove_file(path).unwrap();
-                    }
-                }
-            }
-
-            path.with_file_name(format!("{}{}", if create_subdir {name.to_string() + "/"} else {"".to_string()}, f_name))
-            // path.with_file_name(f_name)
-        };
-
-        let writer = hound::WavWriter::create(&path, *spec);
-        match writer {
-            Ok(mut writer) =>
-            {
-                for sample in write_buf.iter() {
-                    // println!("channel export path: {:?}", path);
-                    writer.write_sample(**sample).unwrap();
-                }
-                return Ok(());
-            },
-            Err(e) => {
-                return Err(format!("couldn't open writer\n{e}\npath: {:?}", path));
-            }
+    if let Some(postfix_str) = std::env::args().into_iter().find(|a| a.starts_with(&auto_cut_postfix_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.numbering_postfix = postfix_str.strip_prefix(&auto_cut_postfix_arg).unwrap().parse().unwrap_or(ac.numbering_postfix.to_string());
         }
     }
 
-
-
-
-
-    /// finds which bit int was used and processes
-    fn setup_wav_processing(&self, path: &Path){
-        println!("Processing wav file: {:?}", path.display());
-        if let Ok(mut reader) = WavReader::open(path) {
-            let bits = reader.spec().bits_per_sample;
-            match reader.spec().sample_format {
-                hound::SampleFormat::Int => {
-                    match bits {
-                        16 => {
-                            self.process_wav::<i16, _>(path, &mut reader, (i16::MAX as f32 * self.deviation_normalized) as i16);
-                        },
-                        24 => {
-                            self.process_wav::<i32, _>(path, &mut reader, (int_bit_to_max(24, true) as f32 * self.deviation_normalized) as i32);
-                        },
-                        32 => {
-                            self.process_wav::<i32, _>(path, &mut reader, (i32::MAX as f32 * self.deviation_normalized) as i32);
-                        },
-                        _ => {
-                            println!("{bits} bit integer samples not supported!");
-                        }
-                    }
-                },
-                hound::SampleFormat::Float => {
-                    match bits {
-                        _ => {
-                            println!("{bits} bit floating point samples not supported!");
-                        }
-                    }
-                }
-            }
+    if std::env::args().any(|a| a == auto_cut_subdir_arg) {
+        if let Some(ac) = &mut auto_cut {
+            ac.create_subdirectory = true;
         }
     }
 
-
-
-
-
-    /// checks if the current dir or file is a .wav file and processes.
-    fn check_file_for_wav(&self, entry: DirEntry) {
-        // println!("looking at path: {}", entry.path().display());
-        if let Some(file_type) = entry.file_type() {
-            if file_type.is_file() {
-                // println!("file name: {:?}", entry.file_name());
-                if let Some(name) = entry.file_name().to_str() {
-                    let name = name.to_lowercase();
-                    let name: Vec<&str> = name.split('.').rev().collect();
-                    if let Some(extention) = name.first() {
-                        if *extention == "wav" {
-                            self.setup_wav_processing(entry.path())
-                        }
-                    }
-                }
-            }
+    if std::env::args().any(|a| a == auto_cut_delete_original_arg) {
+        if let Some(ac) = &mut auto_cut {
+            ac.delete_original = true;
         }
     }
 
-}
-
-
-
-
-
-
-/// Returns (db, is_overwrite, should_delete_empty)
-fn process_args() -> (f32, bool, bool, Option<AutoCut>) {
-    let help_arg = String::from("-h");
-    let db_arg = String::from("-db=");
-    let overwrite_arg = String::from("-o");
-    let delete_empty_arg = String::from("-rm");
-    let auto_cut_arg = String::from("-ac");
-    let auto_cut_min_silence_len_ms_arg = String::from("-acsilence=");
-    let auto_cut_min_sample_len_ms_arg = String::from("-acsample=");
-    let auto_cut_postfix_arg = String::from("-acpostfix=");
-    let auto_cut_subdir_arg = String::from("-acsubdir");
-    let auto_cut_delete_original_arg = String::from("-acdelete");
-
-    let mut db = -60.0;
-    let mut should_overwrite = false;
-    let mut delete_empty = false;
-    let mut auto_cut = None; // default none
-
-    // let mut args_iter = std::env::args().into_iter();
-
-    if let Some(_) = std::env::args().into_iter().find(|b| *b == help_arg) {
-        println!("\t[USAGE]");
-        println!("Looks for a \".wavignore\" file in the current directory which uses the gitignore style (you can put them in subdirectories too)\n\nFinds all .wav files and trims the end silence off.\nIt will also try to cut whole channels if they are empty.");
-        println!("\n\n\t[EXAMPLE]");
-        println!("wav_optimizer.exe -db=-55.7 -o -rm");
-        println!("wav_optimizer.exe -db=-40 -o -rm -ac -acsilence=202.1 -acsample=250 -acpostfix='.'");
-        println!("\n\n\t[OPTIONS]\n-db=\t\tset a float value for the minimum dB the sample should be at the end when trimming. If not specified, it defaults to -60 dB\n\n-o\t\tif specified in the args, will overwrite the input files with the trimmed version. Otherwise it will add a suffix to the name and make a new file.\n\n-rm\t\tIf specified in the args, will delete input files which are deemed empty (because of the '-db' arg).\n\n-ac\t\tWill enable auto cutting up the sample at silences, this will then export multiple smaller files which contain audio data over the threshold.\n\n-acsilence=\tThe minimum amount of milliseconds the samples need to be under the threshold to recognize it as a separate sample.\n\n-acsample=\tThe minimum amount of milliseconds a cut sample needs to be before being recognized as a separate sample.\n\n-acpostfix=\tThe postfix to use before numbering. For example inputfile-01 or inputfile.01.\n\n-acsubdir\tWill add the outputted cuts into a subfolder with the name of the original file.\n\n-acdelete\tWill delete the original (long) sample after creating the cuts.");
-        exit(0);
+    if let Some(db_str) = std::env::args().find(|a| a.starts_with(&auto_cut_close_db_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.close_db = db_str.strip_prefix(&auto_cut_close_db_arg).unwrap().parse().ok();
+        }
     }
 
-    if let Some(db_str) = std::env::args().into_iter().find(|a| a.contains(&db_arg)) {
-        db = db_str.strip_prefix(&db_arg).unwrap().parse().unwrap_or(db);
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&auto_cut_hold_ms_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.hold_ms = ms_str.strip_prefix(&auto_cut_hold_ms_arg).unwrap().parse().unwrap_or(ac.hold_ms);
+        }
     }
 
-    if let Some(_) = std::env::args().into_iter().find(|a| a == &overwrite_arg) {
-        should_overwrite = true;
+    if let Some(output_str) = std::env::args().find(|a| a.starts_with(&auto_cut_output_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.output = CutOutput::from_arg(output_str.strip_prefix(&auto_cut_output_arg).unwrap()).unwrap_or(ac.output);
+        }
     }
 
-    if let Some(_) = std::env::args().into_iter().find(|a| a == &delete_empty_arg) {
-        delete_empty = true;
+    if let Some(formats_str) = std::env::args().find(|a| a.starts_with(&auto_cut_regions_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            ac.region_lists = formats_str.strip_prefix(&auto_cut_regions_arg).unwrap().split(',').filter_map(RegionFormat::from_arg).collect();
+        }
     }
 
-    // NOTE: AutoCut functions
-
-    if let Some(_) = std::env::args().into_iter().find(|a| a == &auto_cut_arg) {
-        auto_cut = Some(AutoCut::default());
+    if std::env::args().any(|a| a == auto_cut_sfz_arg) {
+        if let Some(ac) = &mut auto_cut {
+            ac.instrument.sfz = true;
+        }
     }
 
-    if let Some(ms_str) = std::env::args().into_iter().find(|a| a.contains(&auto_cut_min_silence_len_ms_arg)) {
+    if let Some(layout_str) = std::env::args().find(|a| a.starts_with(&auto_cut_layout_arg)) {
         if let Some(ac) = &mut auto_cut {
-            ac.min_silence_length_ms = ms_str.strip_prefix(&auto_cut_min_silence_len_ms_arg).unwrap().parse().unwrap_or(ac.min_silence_length_ms);
+            ac.instrument.layout = SliceLayout::from_arg(layout_str.strip_prefix(&auto_cut_layout_arg).unwrap()).unwrap_or(ac.instrument.layout);
         }
     }
 
-    if let Some(ms_str) = std::env::args().into_iter().find(|a| a.contains(&auto_cut_min_sample_len_ms_arg)) {
+    if std::env::args().any(|a| a == auto_cut_dspreset_arg) {
         if let Some(ac) = &mut auto_cut {
-            ac.min_length_per_sample_ms = ms_str.strip_prefix(&auto_cut_min_sample_len_ms_arg).unwrap().parse().unwrap_or(ac.min_length_per_sample_ms);
+            ac.instrument.dspreset = true;
         }
     }
 
-    if let Some(postfix_str) = std::env::args().into_iter().find(|a| a.contains(&auto_cut_postfix_arg)) {
+    if let Some(range_str) = std::env::args().find(|a| a.starts_with(&auto_cut_key_range_arg)) {
         if let Some(ac) = &mut auto_cut {
-            ac.numbering_postfix = postfix_str.strip_prefix(&auto_cut_postfix_arg).unwrap().parse().unwrap_or(ac.numbering_postfix.to_string());
+            ac.instrument.key_range = KeyRange::from_arg(range_str.strip_prefix(&auto_cut_key_range_arg).unwrap()).unwrap_or(ac.instrument.key_range);
         }
     }
 
-    if let Some(_) = std::env::args().into_iter().find(|a| a == &auto_cut_subdir_arg) {
+    if let Some(note_str) = std::env::args().find(|a| a.starts_with(&auto_cut_root_arg)) {
         if let Some(ac) = &mut auto_cut {
-            ac.create_subdirectory = true;
+            ac.instrument.root_note = naming::parse_note(note_str.strip_prefix(&auto_cut_root_arg).unwrap());
         }
     }
 
-    if let Some(_) = std::env::args().into_iter().find(|a| a == &auto_cut_delete_original_arg) {
+    if std::env::args().any(|a| a == auto_cut_loop_arg) {
         if let Some(ac) = &mut auto_cut {
-            ac.delete_original = true;
+            ac.instrument.looping = Some(Loop::default());
         }
     }
 
-    (db, should_overwrite, delete_empty, auto_cut)
-}
-
-/// **Returns** the largest number that an `x` bit `(signed?)` integer can store.
-/// # Example
-/// ```rust
-/// assert_eq!(int_bit_to_max(16, true) as i16, i16::MAX);
-/// assert_eq!(int_bit_to_max(16, false) as u16, u16::MAX);
-/// assert_eq!(int_bit_to_max(24, true) as i32, 8388607_i32); // looking for the max of 24 bit, so must be stored in a bigger type
-/// assert_eq!(int_bit_to_max(24, false) as u32, 16777215_u32);
-/// int_bit_to_max
-/// ```
-fn int_bit_to_max(bits: u32, signed: bool) -> u64 {
-    let sign = {
-        if signed {
-            1
-        } else {
-            0
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&auto_cut_loop_start_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            let looping = ac.instrument.looping.get_or_insert_with(Loop::default);
+            looping.start_ms = ms_str.strip_prefix(&auto_cut_loop_start_arg).unwrap().parse().unwrap_or(looping.start_ms);
         }
-    };
-
-    2u64.pow(bits as u32 - sign) - 1
-}
-
-/// Returns the normalized value from decibels.
-/// # Example
-/// ```rust
-/// assert_eq!(db_to_normalized_value(0.0), 1.0);
-/// assert_eq!(db_to_normalized_value(-2.0), 0.63095734448);
-/// assert_eq!(db_to_normalized_value(-60.0), 0.000001);
-/// ```
-#[allow(non_snake_case)]
-fn db_to_normalized_value(dB: f32) -> f32 {
-    10_f32.powf(dB/20f32)
-}
+    }
 
-#[test]
-fn bit_to_max () {
-    assert_eq!(int_bit_to_max(16, true) as i16, i16::MAX);
-    assert_eq!(int_bit_to_max(16, false) as u16, u16::MAX);
-    assert_eq!(int_bit_to_max(24, true) as i32, 8388607_i32);
-    assert_eq!(int_bit_to_max(24, false) as u32, 16777215_u32);
-    assert_eq!(int_bit_to_max(32, true) as i32, i32::MAX);
-    assert_eq!(int_bit_to_max(32, false) as u32, u32::MAX);
-}
+    if let Some(ms_str) = std::env::args().find(|a| a.starts_with(&auto_cut_loop_crossfade_arg)) {
+        if let Some(ac) = &mut auto_cut {
+            let looping = ac.instrument.looping.get_or_insert_with(Loop::default);
+            looping.crossfade_ms = ms_str.strip_prefix(&auto_cut_loop_crossfade_arg).unwrap().parse().unwrap_or(looping.crossfade_ms);
+        }
+    }
 
-#[test]
-fn db_to_normalized() {
-    return ();
-    assert_eq!(db_to_normalized_value(0.0), 1.0);
-    assert_eq!(db_to_normalized_value(-2.0), 0.63095734448);
-    assert_eq!(db_to_normalized_value(-60.0), 0.000001);
+    let mut options = Options {
+        db,
+        noise_floor,
+        overwrite_input: should_overwrite,
+        out_dir,
+        keep_chunks,
+        naming,
+        delete_empty,
+        backup,
+        dry_run,
+        report,
+        paths,
+        jobs,
+        stream,
+        detection,
+        trim,
+        fade,
+        auto_cut,
+    };
+    // a file or folder inside another given folder would be processed twice.
+    options.remove_nested_paths();
+    options
 }
@@ -0,0 +1,143 @@
+//! Naming of the output files.
+
+/// How output files get named.
+#[derive(Debug, Clone)]
+pub struct Naming {
+    /// the file name without `.wav`, `None` keeps the default `{name}_stripped-01` style names.
+    /// Placeholders: `{name}` the original file name, `{index}` the index of the cut, `{start}` the start in ms
+    /// in the original file, `{duration}` the length in ms, `{channels}` the amount of channels, `{note}` a note name.
+    /// `{index}` and `{note}` are left empty in the names of files that aren't cuts.
+    pub template: Option<String>,
+    /// the minimum amount of digits of `{index}`, padded with zeroes.
+    pub index_padding: usize,
+    /// the index of the first cut.
+    pub index_start: usize,
+    /// the MIDI note of the first cut for `{note}`, going up `note_step` semitones with every cut.
+    pub start_note: u8,
+    pub note_step: u8,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Naming {
+            template: None,
+            index_padding: 2,
+            index_start: 1,
+            start_note: 60,
+            note_step: 1,
+        }
+    }
+}
+
+/// Where the audio of an output file comes from.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct OutputInfo {
+    /// the index of the cut, `None` if the file doesn't get cut up.
+    pub slice: Option<usize>,
+    /// the first frame of the output in the original file.
+    pub start: usize,
+    /// the length in frames.
+    pub len: usize,
+    pub channels: usize,
+    pub sample_rate: u32,
+}
+
+impl Naming {
+    /// **Returns** the index of a cut the way it shows up in file names.
+    pub fn format_index(&self, slice: usize) -> String {
+        format!("{:0width$}", slice + self.index_start, width = self.index_padding)
+    }
+
+    /// **Returns** the MIDI note of a cut, capped at the highest note.
+    pub fn note(&self, slice: usize) -> u8 {
+        (self.start_note as usize + slice * self.note_step as usize).min(127) as u8
+    }
+
+    /// fills in the placeholders of `template`, unknown ones are kept as they are.
+    pub(crate) fn render(&self, template: &str, name: &str, output: &OutputInfo) -> String {
+        let to_ms = |frames: usize| (frames as u64 * 1000 / output.sample_rate.max(1) as u64).to_string();
+
+        let mut rendered = String::with_capacity(template.len() + name.len());
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else { break };
+            rendered.push_str(&rest[..open]);
+            let placeholder = &rest[open + 1..open + close];
+            match placeholder {
+                "name" => rendered.push_str(name),
+                "index" => rendered.push_str(&output.slice.map(|slice| self.format_index(slice)).unwrap_or_default()),
+                "start" => rendered.push_str(&to_ms(output.start)),
+                "duration" => rendered.push_str(&to_ms(output.len)),
+                "channels" => rendered.push_str(&output.channels.to_string()),
+                "note" => rendered.push_str(&output.slice.map(|slice| note_name(self.note(slice))).unwrap_or_default()),
+                _ => rendered.push_str(&rest[open..=open + close]),
+            }
+            rest = &rest[open + close + 1..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// **Returns** the name of a MIDI note, 60 being `C4`.
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+/// Parses a MIDI note number or a note name like `C4`, `F#2` or `Eb-1`.
+pub fn parse_note(s: &str) -> Option<u8> {
+    if let Ok(note) = s.parse::<u8>() {
+        return (note <= 127).then_some(note);
+    }
+
+    let mut chars = s.chars();
+    let pitch_class: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.strip_prefix('#') {
+        Some(octave) => (1, octave),
+        None => match rest.strip_prefix('b') {
+            Some(octave) => (-1, octave),
+            None => (0, rest),
+        },
+    };
+    let note = (octave.parse::<i32>().ok()? + 1) * 12 + pitch_class + accidental;
+    u8::try_from(note).ok().filter(|note| *note <= 127)
+}
+
+#[test]
+fn notes() {
+    assert_eq!(note_name(60), "C4");
+    assert_eq!(note_name(0), "C-1");
+    assert_eq!(note_name(70), "A#4");
+    assert_eq!(parse_note("C4"), Some(60));
+    assert_eq!(parse_note("f#2"), Some(42));
+    assert_eq!(parse_note("Eb-1"), Some(3));
+    assert_eq!(parse_note("36"), Some(36));
+    assert_eq!(parse_note("G9"), Some(127));
+    assert_eq!(parse_note("G#9"), None);
+    assert_eq!(parse_note("H2"), None);
+}
+
+#[test]
+fn render_template() {
+    let naming = Naming { index_padding: 3, index_start: 0, start_note: 36, ..Naming::default() };
+    let output = OutputInfo { slice: Some(2), start: 1500, len: 250, channels: 2, sample_rate: 1000 };
+    assert_eq!(
+        naming.render("{name}_{index}_{note}_{start}ms+{duration}_{channels}ch_{other}", "kick", &output),
+        "kick_002_D2_1500ms+250_2ch_{other}"
+    );
+    assert_eq!(naming.render("{name", "kick", &output), "{name");
+    let whole = OutputInfo { slice: None, ..output };
+    assert_eq!(naming.render("{name}_{index}{note}_{start}", "kick", &whole), "kick__1500");
+}
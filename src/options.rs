@@ -0,0 +1,293 @@
+//! The settings of the processor.
+
+use std::path::PathBuf;
+
+use crate::{instrument::Instrument, naming::Naming, regions::RegionFormat, WavProcessor};
+
+/// All settings a [`WavProcessor`] runs with.
+#[derive(Debug)]
+pub struct Options {
+    pub db: f32,
+    /// if set, the threshold of each file is based on its own noise floor instead of `db`.
+    pub noise_floor: Option<NoiseFloor>,
+    pub overwrite_input: bool,
+    /// if set, the outputs are written into this directory with the same layout as the input tree, leaving the originals alone.
+    pub out_dir: Option<PathBuf>,
+    /// copies the metadata chunks (LIST/INFO, bext, cue, smpl, iXML, ...) of the input into every output.
+    pub keep_chunks: bool,
+    pub naming: Naming,
+    pub delete_empty: bool,
+    /// moves the files that would be overwritten or deleted into a new timestamped folder in this directory,
+    /// along with a journal to undo the run with.
+    pub backup: Option<PathBuf>,
+    /// only report what would happen, without writing or deleting anything.
+    pub dry_run: bool,
+    /// where to write the JSON report of all processed files.
+    pub report: Option<PathBuf>,
+    /// the directories to walk and single files to process, the command line tool uses the current directory if it's empty.
+    pub paths: Vec<PathBuf>,
+    /// how many files are processed at the same time, 0 uses one thread per CPU core.
+    pub jobs: usize,
+    /// read files twice instead of loading them into memory, used for everything but auto cut.
+    pub stream: bool,
+    pub detection: Detection,
+    pub trim: Trim,
+    pub fade: Fade,
+    pub auto_cut: Option<AutoCut>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            db: -60.0,
+            noise_floor: None,
+            overwrite_input: false,
+            out_dir: None,
+            keep_chunks: true,
+            naming: Naming::default(),
+            delete_empty: false,
+            backup: None,
+            dry_run: false,
+            report: None,
+            paths: Vec::new(),
+            jobs: 0,
+            stream: false,
+            detection: Detection::Peak,
+            trim: Trim::default(),
+            fade: Fade::default(),
+            auto_cut: None,
+        }
+    }
+}
+
+impl Options {
+    /// drops the paths that are the same as, or inside of, another one of the paths, so no file gets processed twice.
+    /// Paths that don't exist are kept, walking them reports the error.
+    pub fn remove_nested_paths(&mut self) {
+        let canonical: Vec<Option<PathBuf>> = self.paths.iter().map(|p| p.canonicalize().ok()).collect();
+        let is_covered = |i: usize| canonical[i].as_ref().is_some_and(|path| {
+            canonical.iter().enumerate().any(|(j, other)| {
+                // of two equal paths the first one is kept.
+                other.as_ref().is_some_and(|other| if path == other { j < i } else { path.starts_with(other) })
+            })
+        });
+        self.paths = self.paths.iter().enumerate().filter(|(i, _)| !is_covered(*i)).map(|(_, p)| p.clone()).collect();
+    }
+}
+
+/// Estimates the noise floor of a file from a low percentile of its windowed RMS levels.
+#[derive(Debug)]
+pub struct NoiseFloor {
+    /// how many dB above the noise floor the threshold is set.
+    pub above_db: f32,
+    /// the percentile (0 to 100) of the RMS levels which is taken as the noise floor.
+    pub percentile: f32,
+    pub window_ms: f32,
+}
+
+impl Default for NoiseFloor {
+    fn default() -> Self {
+        NoiseFloor {
+            above_db: 10.0,
+            percentile: 10.0,
+            window_ms: 50.0,
+        }
+    }
+}
+
+/// How samples are judged to be over the threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detection {
+    /// every single sample is compared against the threshold.
+    Peak,
+    /// the RMS level over a window (in ms) centered on each sample is compared against the threshold.
+    Rms { window_ms: f32 },
+}
+
+/// Which ends of a file get their silence trimmed off, and how much audio to keep around the cut points.
+#[derive(Debug)]
+pub struct Trim {
+    pub start: bool,
+    pub end: bool,
+    pub pre_roll_ms: f32,
+    pub post_roll_ms: f32,
+    /// if set, the trim and cut points move to the nearest zero crossing within this many ms.
+    pub zero_crossing_window_ms: Option<f32>,
+}
+
+impl Default for Trim {
+    fn default() -> Self {
+        Trim {
+            start: false,
+            end: true,
+            pre_roll_ms: 0.0,
+            post_roll_ms: 0.0,
+            zero_crossing_window_ms: None,
+        }
+    }
+}
+
+/// The curve used for fading in and out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FadeShape {
+    Linear,
+    EqualPower,
+    Exponential,
+}
+
+impl FadeShape {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "linear" => Some(FadeShape::Linear),
+            "equalpower" => Some(FadeShape::EqualPower),
+            "exponential" => Some(FadeShape::Exponential),
+            _ => None,
+        }
+    }
+
+    /// Returns the gain at `x` (0 to 1) along a fade in, a fade out is the same curve mirrored.
+    pub fn gain(self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            FadeShape::Linear => x,
+            FadeShape::EqualPower => (x * std::f64::consts::FRAC_PI_2).sin(),
+            // a straight line in dB from -60 to 0, shifted down so it starts at silence
+            FadeShape::Exponential => (1000_f64.powf(x) - 1.0) / 999.0,
+        }
+    }
+}
+
+/// What auto cut writes for the regions it finds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutOutput {
+    /// every region as a separate file.
+    Files,
+    /// one trimmed file with a cue point and label at the start of every region, for samplers that slice it themselves.
+    Markers,
+    Both,
+}
+
+impl CutOutput {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "files" => Some(CutOutput::Files),
+            "markers" => Some(CutOutput::Markers),
+            "both" => Some(CutOutput::Both),
+            _ => None,
+        }
+    }
+
+    pub fn writes_files(self) -> bool {
+        self != CutOutput::Markers
+    }
+
+    pub fn writes_markers(self) -> bool {
+        self != CutOutput::Files
+    }
+}
+
+/// Fades applied to the start and end of every exported region, to avoid clicks at the cut points.
+#[derive(Debug)]
+pub struct Fade {
+    pub in_ms: f32,
+    pub out_ms: f32,
+    pub shape: FadeShape,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Fade {
+            in_ms: 0.0,
+            out_ms: 0.0,
+            shape: FadeShape::Linear,
+        }
+    }
+}
+
+impl Fade {
+    /// Returns the gain for sample `i` out of a region of `len` samples.
+    pub fn gain_at(&self, i: usize, len: usize, sample_rate: u32) -> f64 {
+        let fade_in = WavProcessor::get_sample_len_from_ms(&self.in_ms, &sample_rate) as usize;
+        let fade_out = WavProcessor::get_sample_len_from_ms(&self.out_ms, &sample_rate) as usize;
+        let from_end = len - 1 - i;
+
+        let mut gain = 1.0;
+        if i < fade_in {
+            gain *= self.shape.gain(i as f64 / fade_in as f64);
+        }
+        if from_end < fade_out {
+            gain *= self.shape.gain(from_end as f64 / fade_out as f64);
+        }
+        gain
+    }
+}
+
+/// Settings for cutting a file up into separate samples at its silences.
+#[derive(Debug)]
+pub struct AutoCut {
+    pub min_silence_length_ms: f32,
+    pub min_length_per_sample_ms: f32,
+    pub numbering_postfix: String,
+    pub create_subdirectory: bool,
+    pub delete_original: bool,
+    /// whether the regions become separate files, markers in one file or both.
+    pub output: CutOutput,
+    /// the region lists written next to the cuts, in any of these formats.
+    pub region_lists: Vec<RegionFormat>,
+    /// the sampler instruments written next to the cut files.
+    pub instrument: Instrument,
+    /// the level in dB the signal has to drop below before a silence starts, `-db` opens it again.
+    pub close_db: Option<f32>,
+    /// how long the signal has to stay below the close threshold before a silence starts.
+    pub hold_ms: f32,
+}
+
+impl Default for AutoCut {
+    fn default() -> Self {
+        AutoCut {
+            min_silence_length_ms: 20.0,
+            min_length_per_sample_ms: 300.0,
+            numbering_postfix: "-".to_string(),
+            create_subdirectory: false,
+            delete_original: false,
+            output: CutOutput::Files,
+            region_lists: Vec::new(),
+            instrument: Instrument::default(),
+            close_db: None,
+            hold_ms: 0.0,
+        }
+    }
+}
+
+
+#[test]
+fn fade_gains() {
+    for shape in [FadeShape::Linear, FadeShape::EqualPower, FadeShape::Exponential] {
+        assert_eq!(shape.gain(0.0), 0.0);
+        assert!((shape.gain(1.0) - 1.0).abs() < 1e-9);
+        assert!(shape.gain(0.25) < shape.gain(0.75));
+    }
+
+    // 10 samples of fade in and out at 1000 Hz
+    let fade = Fade { in_ms: 10.0, out_ms: 10.0, shape: FadeShape::Linear };
+    assert_eq!(fade.gain_at(0, 100, 1000), 0.0);
+    assert_eq!(fade.gain_at(5, 100, 1000), 0.5);
+    assert_eq!(fade.gain_at(50, 100, 1000), 1.0);
+    assert_eq!(fade.gain_at(99, 100, 1000), 0.0);
+}
+
+#[test]
+fn nested_paths() {
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_nested_paths_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib/sub")).unwrap();
+    std::fs::write(dir.join("lib/sub/b1.wav"), b"").unwrap();
+    std::fs::create_dir_all(dir.join("other")).unwrap();
+
+    let mut options = Options {
+        paths: vec![dir.join("lib/sub/b1.wav"), dir.join("lib"), dir.join("lib/./sub"), dir.join("other"), dir.join("lib"), dir.join("missing")],
+        ..Options::default()
+    };
+    options.remove_nested_paths();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(options.paths, vec![dir.join("lib"), dir.join("other"), dir.join("missing")]);
+}
@@ -0,0 +1,1468 @@
+//! The processing of single files and in-memory samples.
+
+use std::{fs::{self, create_dir_all, remove_file}, panic::{self, AssertUnwindSafe}, path::{Component, Path, PathBuf}};
+
+use hound::WavReader;
+use ignore::DirEntry;
+
+use crate::{
+    atomic::write_atomically,
+    backup::{self, Journal},
+    chunks::{self, Chunk},
+    db_to_normalized_value, get_full_scale, normalized_value_to_db,
+    error::{Error, Result},
+    instrument::{self, SliceFile},
+    naming::OutputInfo,
+    options::{AutoCut, Detection, NoiseFloor, Options},
+    regions::Region,
+    report::FileReport,
+    sample::{read_samples, WavSample},
+    wav64,
+};
+
+/// Trims and cuts wav files with the settings it was created with.
+pub struct WavProcessor {
+    pub(crate) deviation_normalized: f32,
+    pub(crate) options: Options,
+    /// where the originals go instead of being overwritten or deleted, if there's a backup directory.
+    pub(crate) journal: Option<Journal>,
+}
+
+/// The audio of a file after trimming, with the empty channels dropped.
+#[derive(Debug, Clone)]
+pub struct TrimmedAudio<T> {
+    /// the spec of the trimmed audio, so only counting the kept channels.
+    pub spec: hound::WavSpec,
+    /// the samples of every kept channel, not interleaved.
+    pub channels: Vec<Vec<T>>,
+    /// the threshold that was used, in raw sample units.
+    pub deviation: T,
+    /// the estimated noise floor in raw sample units, if the threshold was based on it.
+    pub noise_floor: Option<f64>,
+    /// length of the input in samples per channel.
+    pub original_samples: usize,
+    /// the sample of the input the trimmed audio starts at.
+    pub start: usize,
+    /// indices of the input channels that were empty.
+    pub removed_channels: Vec<usize>,
+}
+
+impl WavProcessor {
+    pub fn new(mut options: Options) -> Self {
+        // with an output directory the originals are never touched.
+        if options.out_dir.is_some() {
+            options.delete_empty = false;
+            if let Some(ac) = &mut options.auto_cut {
+                ac.delete_original = false;
+            }
+        }
+
+        // a dry run doesn't change anything, so there's nothing to back up.
+        let journal = options.backup.as_ref().filter(|_| !options.dry_run).map(|root| Journal::new(backup::new_run_dir(root)));
+
+        WavProcessor {
+            deviation_normalized: db_to_normalized_value(options.db),
+            options,
+            journal,
+        }
+    }
+
+
+
+
+    /// **Returns** the backup folder of this run, if there is one.
+    pub fn backup_dir(&self) -> Option<&Path> {
+        self.journal.as_ref().map(|j| j.dir())
+    }
+
+
+
+
+    /// trims the interleaved samples of a file and drops its empty channels, without touching the file system.
+    pub fn trim_samples<T: WavSample>(&self, samples: &[T], spec: hound::WavSpec) -> TrimmedAudio<T> {
+        let (deviation, noise_floor) = match &self.options.noise_floor {
+            Some(nf) => {
+                let (floor, threshold) = self.get_noise_floor_threshold(samples.iter().copied(), &spec, nf);
+                (T::from_f64(threshold), Some(floor))
+            },
+            None => (self.get_deviation(&spec), None),
+        };
+
+        // Create a vector for each channel to store its samples
+        let num_channels = spec.channels as usize;
+        let mut channels: Vec<Vec<T>> = vec![Vec::new(); num_channels];
+
+        for (channel_idx, channel) in channels.iter_mut().enumerate() {
+            let channel_samples: Vec<T> = samples.iter()
+                .enumerate()
+                .filter(|(i, _sample)| i % num_channels == channel_idx)
+                .map(|(_i, sample)| *sample)
+                .collect();
+
+            // Store the un-interleaved samples into the respective channel's vector
+            *channel = channel_samples;
+        }
+
+        let mut non_zeroes = Vec::<usize>::with_capacity(channels.len());
+        let mut first_non_zeroes = Vec::<usize>::with_capacity(channels.len());
+
+        for channel in &channels {
+            let mut _last_non_zero = 0;
+            let mut _first_non_zero: Option<usize> = None;
+            // let max_num = channel.iter().max();
+            // let min_num = channel.iter().min();
+
+            for (i, is_loud) in self.get_loud_samples(channel, deviation, spec.sample_rate).into_iter().enumerate() {
+                if is_loud {
+                    _last_non_zero = i;
+                    _first_non_zero.get_or_insert(i);
+                }
+            }
+            non_zeroes.push(_last_non_zero);
+            first_non_zeroes.push(_first_non_zero.unwrap_or(0));
+            // println!("\n\t[CHANNEL INFO]\nchannel size: {} samples\nlast non zero: {}\nmax: {:?}\nmin: {:?}\nfinal length: {:?}%\n", channel.len(), _last_non_zero, max_num, min_num, _last_non_zero as f32 / channel.len() as f32 * 100f32);
+        }
+
+
+        // keep only channels which aren't empty.
+        let mut new_channels: Vec<Vec<T>> = Vec::with_capacity(num_channels);
+        let mut min_first_non_zero: Option<usize> = None;
+        for (i, non_zero) in non_zeroes.iter().enumerate() {
+            if *non_zero == 0 {
+                continue;
+            }
+
+            new_channels.push(channels[i].clone());
+            min_first_non_zero = Some(min_first_non_zero.map_or(first_non_zeroes[i], |m| m.min(first_non_zeroes[i])));
+        }
+
+        let pre_roll = Self::get_sample_len_from_ms(&self.options.trim.pre_roll_ms, &spec.sample_rate) as usize;
+        let post_roll = Self::get_sample_len_from_ms(&self.options.trim.post_roll_ms, &spec.sample_rate) as usize;
+
+        // the channel with the most energy decides where the zero crossings are
+        let zero_crossing = self.options.trim.zero_crossing_window_ms
+            .map(|ms| Self::get_sample_len_from_ms(&ms, &spec.sample_rate) as usize)
+            .zip(Self::get_loudest_channel(&new_channels));
+
+        // make channels shorter (maximum non zero index), the post roll gets truncated by the channel length itself.
+        if self.options.trim.end && !new_channels.is_empty() {
+            let max_non_zero = non_zeroes.iter().max().unwrap();
+            let mut last_i = (max_non_zero + post_roll).min(new_channels[0].len() - 1);
+            if let Some((window, loudest)) = zero_crossing {
+                last_i = Self::find_nearest_zero_crossing(&new_channels[loudest], last_i, window);
+            }
+            for channel in new_channels.iter_mut() {
+                channel.truncate(last_i + 1);
+            }
+        }
+
+        // and drop everything before the first non zero of the kept channels
+        let mut trim_start = 0_usize;
+        if self.options.trim.start && !new_channels.is_empty() {
+            let mut min_non_zero = min_first_non_zero.unwrap_or(0).saturating_sub(pre_roll);
+            if let Some((window, loudest)) = zero_crossing {
+                min_non_zero = Self::find_nearest_zero_crossing(&new_channels[loudest], min_non_zero, window);
+            }
+            for channel in new_channels.iter_mut() {
+                channel.drain(..min_non_zero);
+            }
+            trim_start = min_non_zero;
+        }
+
+        let dropped_channels: Vec<usize> = non_zeroes.iter().enumerate().filter(|(_i, non_zero)| **non_zero == 0).map(|(i, _non_zero)| i).collect();
+        TrimmedAudio {
+            spec: hound::WavSpec { channels: new_channels.len() as u16, ..spec },
+            channels: new_channels,
+            deviation,
+            noise_floor,
+            original_samples: channels.first().map_or(0, |c| c.len()),
+            start: trim_start,
+            removed_channels: dropped_channels,
+        }
+    }
+
+
+
+
+
+    /// **Returns** the regions of the trimmed audio (inclusive) that auto cut would export as separate files.
+    /// `None` if auto cut is off or the audio doesn't get cut.
+    pub fn find_cut_regions<T: WavSample>(&self, audio: &TrimmedAudio<T>) -> Option<Vec<(usize, usize)>> {
+        let ac = self.options.auto_cut.as_ref()?;
+        let mut ranges = self.get_silence_ranges(&audio.channels, audio.spec.sample_rate, ac, audio.deviation)?;
+        self.get_cut_regions(&mut ranges, &audio.channels, audio.spec.sample_rate, ac)
+    }
+
+
+
+
+
+    /// **Returns** the threshold in raw sample units, floats are already normalized so it can be used as is.
+    pub(crate) fn get_deviation<T: WavSample>(&self, spec: &hound::WavSpec) -> T {
+        match spec.sample_format {
+            hound::SampleFormat::Int => T::from_f64((get_full_scale(spec) * self.deviation_normalized as f64).trunc()),
+            hound::SampleFormat::Float => T::from_f64(self.deviation_normalized as f64),
+        }
+    }
+
+
+
+
+
+    fn process_wav<T: WavSample>(&self, path: &Path, samples: Vec<T>, spec: hound::WavSpec, report: &mut FileReport) {
+        let audio = self.trim_samples(&samples, spec);
+        drop(samples);
+
+        if let Some(floor) = audio.noise_floor {
+            let full_scale = get_full_scale(&spec);
+            report.log(format_args!("\tnoise floor: {:.1} dB, threshold: {:.1} dB", normalized_value_to_db(floor / full_scale), normalized_value_to_db(audio.deviation.to_f64() / full_scale)));
+        }
+
+        let new_len = audio.channels.first().map_or(0, |c| c.len());
+        report.original_samples = audio.original_samples;
+        report.new_samples = new_len;
+        report.removed_channels = audio.removed_channels.clone();
+        if self.options.dry_run {
+            report.log(format_args!("\toriginal length: {} samples, trimmed length: {} samples (starting at sample {}), channels to drop: {:?}", audio.original_samples, new_len, audio.start, audio.removed_channels));
+        }
+
+        // now check for regions that need to be cut and exported separately...
+        if let Some(ac) = &self.options.auto_cut {
+            // NOTE: auto cut that shiii
+            // println!("Auto Cut Detected!!");
+            let mut silence_ranges = self.get_silence_ranges(&audio.channels, spec.sample_rate, ac, audio.deviation);
+            let has_cut = self.try_saving_auto_cuts(&mut silence_ranges, ac, &audio, path, report);
+            if !has_cut {
+                // save new singular wav
+                self.save_or_print_new_wav(&audio.channels, &mut spec.clone(), path, audio.start, report);
+            }
+        }
+        else {
+            // save new singular wav
+            self.save_or_print_new_wav(&audio.channels, &mut spec.clone(), path, audio.start, report);
+        }
+
+        // println!("\n\t================================================\n");
+    }
+
+
+
+
+
+    pub(crate) fn get_sample_len_from_ms(ms: &f32, sample_rate: &u32) -> u32 {
+        (ms / 1000_f32 * (*sample_rate) as f32) as u32
+    }
+
+
+
+
+
+    /// **Returns** the level of every sample in the channel in raw sample units, using the detection mode from the options.
+    pub(crate) fn get_levels<T: WavSample>(&self, channel: &[T], sample_rate: u32) -> Vec<f64> {
+        match self.options.detection {
+            Detection::Peak => channel.iter().map(|s| s.to_f64().abs()).collect(),
+            Detection::Rms { window_ms } => {
+                let half_window = (Self::get_sample_len_from_ms(&window_ms, &sample_rate) as usize / 2).max(1);
+
+                // sliding sum of squares over [i - half_window, i + half_window]
+                let mut levels = Vec::with_capacity(channel.len());
+                let mut sum = 0_f64;
+                let (mut window_start, mut window_end) = (0_usize, 0_usize);
+                for i in 0..channel.len() {
+                    let wanted_end = (i + half_window + 1).min(channel.len());
+                    while window_end < wanted_end {
+                        sum += channel[window_end].to_f64().powi(2);
+                        window_end += 1;
+                    }
+                    while window_start + half_window < i {
+                        sum -= channel[window_start].to_f64().powi(2);
+                        window_start += 1;
+                    }
+
+                    levels.push((sum.max(0.0) / (window_end - window_start) as f64).sqrt());
+                }
+                levels
+            }
+        }
+    }
+
+
+
+
+
+    /// **Returns** (noise floor, threshold) in raw sample units.
+    /// The noise floor is a low percentile of the RMS levels over consecutive windows, with all channels mixed together.
+    /// Windows of digital silence are left out, they'd put the floor at zero. If there's nothing but silence, the `-db` threshold is used.
+    /// Takes the samples as an iterator, so files that are streamed don't need to be loaded into memory for it.
+    pub(crate) fn get_noise_floor_threshold<T: WavSample>(&self, samples: impl IntoIterator<Item = T>, spec: &hound::WavSpec, nf: &NoiseFloor) -> (f64, f64) {
+        let num_channels = (spec.channels as usize).max(1);
+        let window = (Self::get_sample_len_from_ms(&nf.window_ms, &spec.sample_rate) as usize).max(1) * num_channels;
+
+        let mut levels: Vec<f64> = Vec::new();
+        let (mut sum, mut count) = (0_f64, 0_usize);
+        for sample in samples {
+            sum += sample.to_f64().powi(2);
+            count += 1;
+            if count == window {
+                levels.push((sum / count as f64).sqrt());
+                (sum, count) = (0.0, 0);
+            }
+        }
+        if count > 0 {
+            levels.push((sum / count as f64).sqrt());
+        }
+        levels.retain(|level| *level > 0.0);
+        if levels.is_empty() {
+            return (0.0, self.get_deviation::<f64>(spec));
+        }
+        levels.sort_by(|a, b| a.total_cmp(b));
+
+        let i = ((nf.percentile.clamp(0.0, 100.0) / 100.0) * (levels.len() - 1) as f32).round() as usize;
+        let floor = levels[i];
+        (floor, floor * db_to_normalized_value(nf.above_db) as f64)
+    }
+
+
+
+
+
+    /// **Returns** for every sample in the channel whether it's over the threshold.
+    fn get_loud_samples<T: WavSample>(&self, channel: &[T], deviation: T, sample_rate: u32) -> Vec<bool> {
+        let threshold = deviation.to_f64().abs();
+        self.get_levels(channel, sample_rate).into_iter().map(|level| level > threshold).collect()
+    }
+
+
+
+
+
+    /// **Returns** for every sample in the channel whether the gate is open.
+    /// The gate opens above `open`, and only closes after the level stayed below `close` for `hold` samples.
+    /// The samples in that hold time are counted as silence again.
+    fn get_gated_samples<T: WavSample>(&self, channel: &[T], open: f64, close: f64, hold: usize, sample_rate: u32) -> Vec<bool> {
+        let levels = self.get_levels(channel, sample_rate);
+        let mut is_open_vec = vec![false; levels.len()];
+        let mut is_open = false;
+        let mut below_since: Option<usize> = None;
+
+        for (i, level) in levels.iter().enumerate() {
+            if !is_open {
+                is_open = *level > open;
+            } else if *level >= close {
+                below_since = None;
+            } else {
+                let start = *below_since.get_or_insert(i);
+                if i - start + 1 >= hold.max(1) {
+                    is_open = false;
+                    below_since = None;
+                    is_open_vec[start..i].fill(false);
+                }
+            }
+            is_open_vec[i] = is_open;
+        }
+
+        // the file ended while the level was already going down
+        if let Some(start) = below_since {
+            is_open_vec[start..].fill(false);
+        }
+        is_open_vec
+    }
+
+
+
+
+
+    /// **Returns** the index of the channel with the highest energy (sum of squares).
+    fn get_loudest_channel<T: WavSample>(channels: &[Vec<T>]) -> Option<usize> {
+        channels.iter()
+            .map(|channel| channel.iter().map(|s| s.to_f64() * s.to_f64()).sum::<f64>())
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _energy)| i)
+    }
+
+
+
+
+
+    /// **Returns** the index closest to `index` (at most `window` samples away) where the channel crosses zero.
+    /// At a crossing the sample closest to zero is picked. If there is no crossing, `index` is returned as is.
+    pub(crate) fn find_nearest_zero_crossing<T: WavSample>(channel: &[T], index: usize, window: usize) -> usize {
+        let is_crossing = |i: usize| -> Option<usize> {
+            let this = channel.get(i)?.to_f64();
+            if this == 0.0 { return Some(i); }
+            let next = channel.get(i + 1)?.to_f64();
+            if (this < 0.0) != (next < 0.0) {
+                return Some(if this.abs() <= next.abs() || i + 1 > index + window { i } else { i + 1 });
+            }
+            None
+        };
+
+        for distance in 0..=window {
+            if let Some(i) = index.checked_add(distance).and_then(is_crossing) { return i; }
+            if let Some(i) = index.checked_sub(distance).and_then(is_crossing) { return i; }
+        }
+        index
+    }
+
+
+
+
+
+    fn get_silence_ranges<T: WavSample>(&self, channels: &[Vec<T>], sample_rate: u32, ac: &AutoCut, deviation: T) -> Option<Vec<(usize, usize)>> {
+        let silence_min_length_samples = WavProcessor::get_sample_len_from_ms(&ac.min_silence_length_ms, &sample_rate);
+        // let sample_min_length_samples = WavProcessor::get_sample_len_from_ms(&ac.min_length_per_sample_ms, &sample_rate);
+        // println!("min silence length: {}, min samples length: {}", silence_min_length_samples, sample_min_length_samples);
+
+        let mut silence_ranges_per_channel: Vec<Vec<(usize, usize)>> = Vec::with_capacity(10);
+
+        // the close threshold is relative to the open one, so it follows the threshold of the file.
+        let is_gated = ac.close_db.is_some() || ac.hold_ms > 0.0;
+        let open = deviation.to_f64().abs();
+        let close = open * db_to_normalized_value(ac.close_db.unwrap_or(self.options.db).min(self.options.db) - self.options.db) as f64;
+        let hold = WavProcessor::get_sample_len_from_ms(&ac.hold_ms, &sample_rate) as usize;
+
+        for channel in channels {
+            let mut silence_ranges_vec: Vec<(usize, usize)> = Vec::with_capacity(5);
+            let (mut silence_start, mut silence_end) = (0_usize, 0_usize);
+            let mut is_checking_silence = false;
+
+            let loud_samples = if is_gated {
+                self.get_gated_samples(channel, open, close, hold, sample_rate)
+            } else {
+                self.get_loud_samples(channel, deviation, sample_rate)
+            };
+
+            for (i, is_loud) in loud_samples.into_iter().enumerate() {
+                if !is_loud {
+                    // found a zero.
+                    if !is_checking_silence {
+                        silence_start = i;
+                        silence_end = i;
+                        is_checking_silence = true;
+                    } else {
+                        silence_end = i; // this makes it inclusive. so [start:end]
+                    }
+                } else if is_checking_silence {
+                    is_checking_silence = false;
+                    // check if lengths are in margin.
+                    if silence_end - silence_start >= silence_min_length_samples as usize {
+                        silence_ranges_vec.push((silence_start, silence_end));
+                    }
+                }
+            }
+
+            if !silence_ranges_vec.is_empty() {
+                silence_ranges_per_channel.push(silence_ranges_vec);
+            }
+        }
+
+        // println!("amount of silences per channel: {}\nsilences: {:?}", if silence_ranges_per_channel.len() > 0 { silence_ranges_per_channel[0].len() } else { 0 }, &silence_ranges_per_channel);
+
+        let final_silences_all_channels: Option<Vec<(usize, usize)>> = {
+            if silence_ranges_per_channel.len() == 1 { Some(silence_ranges_per_channel[0].clone()) }
+            else if silence_ranges_per_channel.is_empty() { None }
+            else {
+                // find the common grounds for each channel.
+                // now make slices based on the silences in EACH channel, let's not forget that there
+                // are multiple ones so make sure they don't cut away samples.
+                let mut v: Vec<(usize, usize)> = Vec::with_capacity(5);
+
+                // find the largest vec first
+                let most_silences_vec = silence_ranges_per_channel.iter().max_by_key(|v| v.len())?;
+
+                // if other start > this start but <= this.end, then this.start = other.start
+                // if other end > this start but <= this.end, then this.end = other.end
+                for this_silence in &mut most_silences_vec.clone() {
+                    for other_vec in &silence_ranges_per_channel[0..] {
+                        let mut found_silence = false;
+                        for other_silence in other_vec {
+                            if other_silence.0 >= this_silence.0 && other_silence.0 <= this_silence.1 { this_silence.0 = other_silence.0; found_silence = true; }
+                            if other_silence.1 >= this_silence.0 && other_silence.1 <= this_silence.1 { this_silence.1 = other_silence.1; found_silence = true; }
+                            if found_silence { break }
+                        }
+                    }
+
+                    v.push(*this_silence);
+                }
+
+                // println!("final silences:\t\t\t\t{:?}", &v);
+                if !v.is_empty() { Some(v) } else { None }
+            }
+        };
+
+        final_silences_all_channels
+    }
+
+
+
+
+
+
+    /// filters the silences that are too short, or would leave too short samples, out of `ranges`.
+    /// **Returns** the regions of `channels` between the remaining silences, padded with the pre and post roll.
+    fn get_cut_regions<T: WavSample>(&self, ranges: &mut Vec<(usize, usize)>, channels: &[Vec<T>], sample_rate: u32, ac: &AutoCut) -> Option<Vec<(usize, usize)>> {
+        let len = channels.first().map_or(0, |c| c.len());
+        let mut remove_idxs: Vec<usize> = Vec::with_capacity(5);
+        // Check lengths if they are still applicable with the ac settings
+        let min_silence_len = Self::get_sample_len_from_ms(&ac.min_silence_length_ms, &sample_rate) as usize;
+        let min_sample_len = Self::get_sample_len_from_ms(&ac.min_length_per_sample_ms, &sample_rate) as usize;
+        for (i, range) in ranges.iter().enumerate() {
+            if range.1 - range.0 < min_silence_len {
+                remove_idxs.push(i);
+            }
+
+        }
+        // remove all silences that were too short
+        for i in remove_idxs.iter().rev() { // reverse to make sure indexes don't change because originally they go up, which would do -1 at each number
+            ranges.remove(*i);
+        }
+
+        remove_idxs.clear();
+
+        if ranges.is_empty() { return None; }
+
+        // check if sample lengths are still good
+        for (i, range) in ranges.iter().enumerate() {
+            if i == 0 {
+                // check if the space before is long enough, if not, remove this silence range.
+                if range.0 < min_sample_len {
+                    remove_idxs.push(i);
+                    continue;
+                }
+            }
+            if i + 2 >= ranges.len() {
+                if len - range.1 < min_sample_len {
+                    if !remove_idxs.contains(&i) { remove_idxs.push(i); }
+                    continue;
+                }
+            } else {
+                let next_range = ranges[i+1];
+                if next_range.0 - range.1 < min_sample_len {
+                    // remove next cut range
+                    remove_idxs.push(i+1);
+                }
+            }
+        }
+
+        for i in remove_idxs.iter().rev() { // reverse to make sure indexes don't change because originally they go up, which would do -1 at each number
+            ranges.remove(*i);
+        }
+
+        // println!("final ranges after length checks:\t{:?}", &ranges);
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let pre_roll = Self::get_sample_len_from_ms(&self.options.trim.pre_roll_ms, &sample_rate) as usize;
+        let post_roll = Self::get_sample_len_from_ms(&self.options.trim.post_roll_ms, &sample_rate) as usize;
+        let last_i = len - 1;
+
+        let mut regions: Vec<(usize, usize)> = Vec::with_capacity(ranges.len()+2); // +2 for before and after the cuts
+        let mut start_i = 0_usize;
+        for range in ranges.iter() {
+            // pad into the silence, but never past the file bounds
+            let end_i = (range.0 + post_roll).min(last_i);
+            regions.push((start_i, end_i));
+
+            start_i = range.1.saturating_sub(pre_roll);
+        }
+
+        // one more time to get the remainder of the samples
+        regions.push((start_i, last_i));
+
+        // move the padded cut points onto zero crossings, as long as the region stays valid. The ends of the audio stay where they are.
+        let zero_crossing = self.options.trim.zero_crossing_window_ms
+            .map(|ms| Self::get_sample_len_from_ms(&ms, &sample_rate) as usize)
+            .zip(Self::get_loudest_channel(channels));
+        if let Some((window, loudest)) = zero_crossing {
+            let snap = |i: usize| Self::find_nearest_zero_crossing(&channels[loudest], i, window);
+            let last = regions.len() - 1;
+            for (i, region) in regions.iter_mut().enumerate() {
+                let start = if i == 0 { region.0 } else { snap(region.0) };
+                let end = if i == last { region.1 } else { snap(region.1) };
+                if start <= end {
+                    *region = (start, end);
+                }
+            }
+        }
+        Some(regions)
+    }
+
+
+
+
+
+
+    /// saves every region between the silences as a separate file, and/or as markers in a single trimmed file.
+    /// **Returns** false if the file doesn't get cut, in which case nothing was saved.
+    fn try_saving_auto_cuts<T: WavSample>(&self, silence_ranges: &mut Option<Vec<(usize, usize)>>, ac: &AutoCut, audio: &TrimmedAudio<T>, path: &Path, report: &mut FileReport) -> bool {
+        let Some(ranges) = silence_ranges else {
+            return false;
+        };
+        let Some(regions) = self.get_cut_regions(ranges, &audio.channels, audio.spec.sample_rate, ac) else {
+            return false;
+        };
+        report.silence_ranges = ranges.clone();
+
+        let slice_output = |i: usize, region: &(usize, usize)| OutputInfo {
+            slice: Some(i),
+            start: audio.start + region.0,
+            len: region.1 + 1 - region.0,
+            channels: audio.channels.len(),
+            sample_rate: audio.spec.sample_rate,
+        };
+
+        // the file with the markers holds the whole trimmed audio.
+        let marker_output = OutputInfo {
+            slice: None,
+            start: audio.start,
+            len: audio.channels[0].len(),
+            channels: audio.channels.len(),
+            sample_rate: audio.spec.sample_rate,
+        };
+
+        // nothing gets written if the outputs would overwrite each other.
+        let result = self.get_slice_paths(&regions, &slice_output, path).and_then(|slice_paths| {
+            if !(ac.output.writes_markers() && ac.output.writes_files()) {
+                return Ok(());
+            }
+            let marker_path = self.get_output_path(path, &marker_output)?;
+            if slice_paths.contains(&marker_path) {
+                return Err(Error::Path {
+                    path: path.to_path_buf(),
+                    message: format!("the naming template names both the file with the markers and a cut {:?}", marker_path),
+                });
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            Self::push_error(report, e);
+            return true;
+        }
+
+        if ac.output.writes_markers() {
+            self.save_cut_markers(&regions, &slice_output, audio, &marker_output, path, report);
+        }
+        if !ac.region_lists.is_empty() {
+            self.save_region_lists(&regions, &slice_output, ac, audio.spec.sample_rate, path, report);
+        }
+        if ac.output.writes_files() && ac.instrument.is_enabled() {
+            self.save_instruments(&regions, &slice_output, ac, path, report);
+        }
+
+        // save all samples that aren't in the ranges separately
+        // println!("Outputting {} samples", regions.len());
+        for (i, region) in regions.iter().enumerate().filter(|_| ac.output.writes_files()) {
+            let output = slice_output(i, region);
+            if self.options.dry_run {
+                match self.get_output_path(path, &output) {
+                    Ok(out_path) => report.log(format_args!("\twould write cut {} (samples {}..={} of the trimmed audio) to {:?}", i+1, region.0, region.1, out_path)),
+                    Err(e) => Self::push_error(report, e),
+                }
+                continue;
+            }
+            let channels: Vec<Vec<T>> = audio.channels.iter().map(|channel| channel[region.0..=region.1].to_vec()).collect();
+            if let Err(e) = self.save_new_wav::<T>(&channels, &mut audio.spec.clone(), path, &output, &[], report) {
+                Self::push_error(report, e);
+            }
+        }
+        // with '-o' the marked file took the place of the original.
+        let is_overwritten = report.outputs.iter().any(|out| out == path);
+        if self.options.dry_run && ac.delete_original {
+            report.log(format_args!("\twould delete the original: {:?}", path));
+        }
+        // the original is only deleted once every cut is safely written.
+        else if ac.delete_original && report.is_ok() && !is_overwritten && path.is_file() {
+            match self.delete_file(path) {
+                Ok(()) => report.deleted.push(path.to_path_buf()),
+                Err(e) => Self::push_error(report, e),
+            }
+        }
+
+        true
+    }
+
+
+
+
+
+    /// saves the whole trimmed audio as one file, with a cue point and label at the start of every region.
+    /// The labels are the names the regions would get as separate files.
+    fn save_cut_markers<T: WavSample>(&self, regions: &[(usize, usize)], slice_output: &dyn Fn(usize, &(usize, usize)) -> OutputInfo, audio: &TrimmedAudio<T>, output: &OutputInfo, path: &Path, report: &mut FileReport) {
+        let mut markers = Vec::with_capacity(regions.len());
+        for (i, region) in regions.iter().enumerate() {
+            match self.get_output_path(path, &slice_output(i, region)) {
+                Ok(slice_path) => markers.push(chunks::Marker {
+                    start: region.0,
+                    len: region.1 + 1 - region.0,
+                    label: slice_path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                }),
+                Err(e) => return Self::push_error(report, e),
+            }
+        }
+
+        if self.options.dry_run {
+            match self.get_output_path(path, output) {
+                Ok(out_path) => report.log(format_args!("\twould write {} cut markers ({}) to {:?}", markers.len(), markers.iter().map(|m| m.label.as_str()).collect::<Vec<_>>().join(", "), out_path)),
+                Err(e) => Self::push_error(report, e),
+            }
+            return;
+        }
+        if let Err(e) = self.save_new_wav::<T>(&audio.channels, &mut audio.spec.clone(), path, output, &chunks::marker_chunks(&markers), report) {
+            Self::push_error(report, e);
+        }
+    }
+
+
+
+
+
+
+    /// writes the cut regions in every format of `ac.region_lists`, named after the input and next to where its outputs go.
+    /// The positions are in frames of the input file.
+    fn save_region_lists(&self, regions: &[(usize, usize)], slice_output: &dyn Fn(usize, &(usize, usize)) -> OutputInfo, ac: &AutoCut, sample_rate: u32, path: &Path, report: &mut FileReport) {
+        let slice_paths = match self.get_slice_paths(regions, slice_output, path) {
+            Ok(slice_paths) => slice_paths,
+            Err(e) => return Self::push_error(report, e),
+        };
+        let list: Vec<Region> = regions.iter().zip(&slice_paths).enumerate().map(|(i, (region, slice_path))| {
+            let output = slice_output(i, region);
+            Region {
+                index: i + self.options.naming.index_start,
+                start: output.start,
+                end: output.start + output.len,
+                file: slice_path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            }
+        }).collect();
+
+        for format in &ac.region_lists {
+            match self.get_sidecar_path(path, &slice_output(0, &regions[0]), format.suffix()) {
+                Ok(list_path) => self.save_sidecar(&list_path, &format.render(&list, sample_rate), report),
+                Err(e) => Self::push_error(report, e),
+            }
+        }
+    }
+
+
+
+
+
+    /// writes the sampler instruments of `ac.instrument` that map the cut files, next to them.
+    fn save_instruments(&self, regions: &[(usize, usize)], slice_output: &dyn Fn(usize, &(usize, usize)) -> OutputInfo, ac: &AutoCut, path: &Path, report: &mut FileReport) {
+        let slice_paths = match self.get_slice_paths(regions, slice_output, path) {
+            Ok(slice_paths) => slice_paths,
+            Err(e) => return Self::push_error(report, e),
+        };
+        let source = path.file_name().unwrap_or_default().to_string_lossy();
+        let sample_rate = slice_output(0, &regions[0]).sample_rate;
+
+        let formats = [(ac.instrument.sfz, ".sfz"), (ac.instrument.dspreset, ".dspreset")];
+        for (_, suffix) in formats.into_iter().filter(|(enabled, _)| *enabled) {
+            let instrument_path = match self.get_sidecar_path(path, &slice_output(0, &regions[0]), suffix) {
+                Ok(instrument_path) => instrument_path,
+                Err(e) => {
+                    Self::push_error(report, e);
+                    continue;
+                },
+            };
+            // the samples are referred to relative to the instrument, so the folder can be moved as a whole.
+            let dir = instrument_path.parent().unwrap_or(Path::new(""));
+            let slices: Vec<SliceFile> = regions.iter().zip(&slice_paths).enumerate().map(|(i, (region, slice_path))| SliceFile {
+                path: instrument::relative_path(dir, slice_path),
+                len: slice_output(i, region).len,
+            }).collect();
+            let zones = instrument::zones(&slices, &self.options.naming, &ac.instrument, sample_rate);
+            if zones.len() < slices.len() {
+                report.log(format_args!("\tWARNING: only {} cuts fit in the instrument {:?}, the last {} are left out", zones.len(), instrument_path, slices.len() - zones.len()));
+            }
+            let contents = match suffix {
+                ".sfz" => instrument::render_sfz(&zones, &source, sample_rate),
+                _ => instrument::render_dspreset(&zones, &source),
+            };
+            self.save_sidecar(&instrument_path, &contents, report);
+        }
+    }
+
+
+
+
+
+    /// **Returns** the paths the cuts of `regions` get written to.
+    /// Fails if a naming template gives several cuts the same path, they would overwrite each other.
+    fn get_slice_paths(&self, regions: &[(usize, usize)], slice_output: &dyn Fn(usize, &(usize, usize)) -> OutputInfo, path: &Path) -> Result<Vec<PathBuf>> {
+        let slice_paths: Vec<PathBuf> = regions.iter().enumerate().map(|(i, region)| self.get_output_path(path, &slice_output(i, region))).collect::<Result<_>>()?;
+        if let Some(duplicate) = slice_paths.iter().enumerate().find_map(|(i, p)| slice_paths[..i].contains(p).then_some(p)) {
+            return Err(Error::Path {
+                path: path.to_path_buf(),
+                message: format!("the naming template names more than one cut {:?}, it needs a placeholder that differs per cut like {{index}}", duplicate),
+            });
+        }
+        Ok(slice_paths)
+    }
+
+
+
+
+
+    /// **Returns** the path of a file that goes along with the outputs of `path`: named after the input with
+    /// `suffix` added, in the folder the trimmed file would go. `output` is any of the outputs.
+    fn get_sidecar_path(&self, path: &Path, output: &OutputInfo, suffix: &str) -> Result<PathBuf> {
+        let out_path = self.get_output_path(path, &OutputInfo { slice: None, ..*output })?;
+        let mut name = path.file_stem().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        Ok(out_path.with_file_name(name))
+    }
+
+
+
+
+
+    /// writes a text file that goes along with the outputs, or logs it on a dry run.
+    fn save_sidecar(&self, sidecar_path: &Path, contents: &str, report: &mut FileReport) {
+        if self.options.dry_run {
+            report.log(format_args!("\twould write {:?}", sidecar_path));
+            return;
+        }
+        let result = self.create_output_dir(sidecar_path).and_then(|_| {
+            write_atomically(sidecar_path, self.journal.as_ref(), |temp_path| fs::write(temp_path, contents).map_err(|e| Error::io(temp_path, e)))
+        });
+        match result {
+            Ok(()) => report.outputs.push(sidecar_path.to_path_buf()),
+            Err(e) => Self::push_error(report, e),
+        }
+    }
+
+
+
+
+
+
+    /// saves channel data into the path that was passed in.
+    /// `markers` are extra chunks that take the place of the cue points of the original.
+    fn save_new_wav<T: WavSample>(&self, channels: &[Vec<T>], spec: &mut hound::WavSpec, path: &Path, output: &OutputInfo, markers: &[Chunk], report: &mut FileReport) -> Result<()> {
+        spec.channels = channels.len() as u16;
+        let samples_per_channel = {if !channels.is_empty() {channels[0].len()} else {0}};
+        if samples_per_channel == 0 {
+            if self.options.delete_empty {
+                report.log(format_args!("deleting file because it's empty: {:?}", path));
+                self.delete_file(path)?;
+                report.deleted.push(path.to_path_buf());
+            }
+            return Ok(());
+        }
+
+        // add samples interweaved, fading in and out on the way
+        let fade = &self.options.fade;
+        let has_fade = fade.in_ms > 0.0 || fade.out_ms > 0.0;
+        let mut write_buf: Vec<T> = Vec::with_capacity(samples_per_channel * channels.len());
+        for sample in 0..samples_per_channel {
+            let gain = if has_fade { fade.gain_at(sample, samples_per_channel, spec.sample_rate) } else { 1.0 };
+            for channel in channels.iter() {
+                if gain < 1.0 {
+                    write_buf.push(T::from_f64(channel[sample].to_f64() * gain));
+                } else {
+                    write_buf.push(channel[sample]);
+                }
+            }
+        }
+
+        // write new buffer
+        let source = path;
+        let path = {
+            // check if you should create a subdirectory
+            let new_path = self.get_output_path(path, output)?;
+            self.create_output_dir(&new_path)?;
+
+            new_path
+        };
+
+        // never write straight over a file, an interrupted write would leave it corrupt.
+        write_atomically(&path, self.journal.as_ref(), |temp_path| {
+            T::write_wav(temp_path, *spec, &write_buf)?;
+            self.copy_chunks(source, temp_path, output.start, output.len, markers)
+        })?;
+        report.outputs.push(path);
+        Ok(())
+    }
+
+
+
+
+
+    /// **Returns** the path that `save_new_wav` writes to, without touching the file system.
+    pub(crate) fn get_output_path(&self, path: &Path, output: &OutputInfo) -> Result<PathBuf> {
+        let name: &str = match path.file_stem() {
+            Some(name) => name.to_str().ok_or_else(|| Error::Path { path: path.to_path_buf(), message: "the file name isn't valid unicode".to_string() })?,
+            _ => "default_name"
+        };
+        let naming = &self.options.naming;
+        let f_name = match &naming.template {
+            Some(template) => naming.render(template, name, output),
+            None => {
+                let postfix = match (output.slice, &self.options.auto_cut) {
+                    (Some(slice), Some(ac)) => ac.numbering_postfix.clone() + &naming.format_index(slice),
+                    _ => String::new(),
+                };
+                // the originals can't be overwritten in a separate output directory, so the names stay clean there.
+                let is_clean = self.options.overwrite_input || self.options.out_dir.is_some();
+                format!("{name}{}{}", if is_clean {""} else {"_stripped"} , postfix)
+            },
+        };
+
+        let create_subdir = self.options.auto_cut.as_ref().is_some_and(|ac| ac.create_subdirectory);
+        let get_path = |f_name: &str| {
+            let f_name = format!("{}{}.wav", if create_subdir {name.to_string() + "/"} else {"".to_string()}, f_name);
+            match &self.options.out_dir {
+                Some(out_dir) => out_dir.join(self.get_relative_dir(path)).join(f_name),
+                None => path.with_file_name(f_name),
+            }
+        };
+        let out_path = get_path(&f_name);
+
+        // a template can render the name of the input itself, which only '-o' may overwrite.
+        let normalize = |p: &Path| p.components().filter(|c| *c != Component::CurDir).collect::<PathBuf>();
+        if naming.template.is_some() && !self.options.overwrite_input && normalize(&out_path).as_os_str().eq_ignore_ascii_case(normalize(path).as_os_str()) {
+            return Ok(get_path(&format!("{f_name}_stripped")));
+        }
+        Ok(out_path)
+    }
+
+
+
+
+
+    /// **Returns** the folder of `path` relative to the root it was found in, so the output directory mirrors the input tree.
+    /// Files outside of every root end up in the output directory itself.
+    fn get_relative_dir(&self, path: &Path) -> PathBuf {
+        let current_dir = [PathBuf::from(".")];
+        let roots = if self.options.paths.is_empty() { &current_dir[..] } else { &self.options.paths[..] };
+        // `./lib` and `lib` are the same root.
+        let normalize = |p: &Path| p.components().filter(|c| *c != Component::CurDir).collect::<PathBuf>();
+        let path = normalize(path);
+        roots.iter()
+            .filter_map(|root| path.strip_prefix(normalize(root)).ok())
+            .min_by_key(|relative| relative.components().count())
+            .and_then(|relative| relative.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+
+
+
+
+
+    /// creates the folder an output file goes into, if it's a new one.
+    /// Besides the output directory and the auto cut subdirectories, a naming template can contain folders too.
+    pub(crate) fn create_output_dir(&self, out_path: &Path) -> Result<()> {
+        if let Some(dir) = out_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            self.create_dir(dir)?;
+            // println!("Made dir at path: {:?}", dir);
+        }
+        Ok(())
+    }
+
+
+
+
+
+    /// creates `dir` and its missing parents, noting them down in the journal so undoing removes them again.
+    pub fn create_dir(&self, dir: &Path) -> Result<()> {
+        if dir.is_dir() {
+            return Ok(());
+        }
+        if let Some(journal) = &self.journal {
+            let mut missing: Vec<&Path> = dir.ancestors().take_while(|d| !d.as_os_str().is_empty() && !d.exists()).collect();
+            missing.reverse();
+            for d in missing {
+                journal.record_created_dir(d)?;
+            }
+        }
+        // several files can land in the same folder at once, which `create_dir_all` doesn't mind.
+        create_dir_all(dir).map_err(|e| Error::io(dir, e))
+    }
+
+
+
+
+
+    /// copies the metadata chunks of `source` into the freshly written `target`, which holds `len` frames from
+    /// `start` on of the source. Cue points, loops and time references are moved along.
+    /// If there are `markers`, they're written instead of the cue points and labels of the source.
+    pub(crate) fn copy_chunks(&self, source: &Path, target: &Path, start: usize, len: usize, markers: &[Chunk]) -> Result<()> {
+        let mut new_chunks = if self.options.keep_chunks {
+            chunks::adjust_chunks(&chunks::read_chunks(source)?, start, len)
+        } else {
+            Vec::new()
+        };
+        if !markers.is_empty() {
+            new_chunks.retain(|chunk| &chunk.id != b"cue " && !chunks::is_adtl(chunk));
+            new_chunks.extend_from_slice(markers);
+        }
+        chunks::append_chunks(target, &new_chunks)
+    }
+
+
+
+
+
+    /// deletes an input file, or moves it into the backup folder if there is one.
+    pub(crate) fn delete_file(&self, path: &Path) -> Result<()> {
+        match &self.journal {
+            Some(journal) => journal.back_up(path, false),
+            None => remove_file(path).map_err(|e| Error::io(path, e)),
+        }
+    }
+
+
+
+
+
+    /// saves the channels like `save_new_wav`, or only prints what would be saved on a dry run.
+    /// `start` is where the channels start in the original file.
+    pub(crate) fn save_or_print_new_wav<T: WavSample>(&self, channels: &[Vec<T>], spec: &mut hound::WavSpec, path: &Path, start: usize, report: &mut FileReport) {
+        let samples_per_channel = channels.first().map_or(0, |c| c.len());
+        let output = OutputInfo { slice: None, start, len: samples_per_channel, channels: channels.len(), sample_rate: spec.sample_rate };
+        if !self.options.dry_run {
+            if let Err(e) = self.save_new_wav::<T>(channels, spec, path, &output, &[], report) {
+                Self::push_error(report, e);
+            }
+            return;
+        }
+
+        if samples_per_channel == 0 {
+            if self.options.delete_empty {
+                report.log(format_args!("\twould delete the file because it's empty: {:?}", path));
+            } else {
+                report.log(format_args!("\tthe file is empty, nothing would be written"));
+            }
+        } else {
+            match self.get_output_path(path, &output) {
+                Ok(out_path) => report.log(format_args!("\twould write {} samples in {} channels to {:?}", samples_per_channel, channels.len(), out_path)),
+                Err(e) => Self::push_error(report, e),
+            }
+        }
+    }
+
+
+
+
+
+    /// logs the error and records it in the report, so the other files can still be processed.
+    pub(crate) fn push_error(report: &mut FileReport, e: Error) {
+        report.log(format_args!("ERROR: {e}"));
+        report.errors.push(e);
+    }
+
+
+
+
+
+    /// finds which sample format was used and processes
+    /// **Returns** the report of the file, which holds every error that happened to it.
+    pub fn setup_wav_processing(&self, path: &Path) -> FileReport {
+        let mut report = FileReport::new(path);
+        report.log(format_args!("Processing wav file: {:?}", path.display()));
+        let original_size = fs::metadata(path).map_or(0, |m| m.len());
+        // a panic would leave the other threads of the walker waiting, so it's reported like any other error.
+        match panic::catch_unwind(AssertUnwindSafe(|| self.process_wav_file(path, &mut report))) {
+            Ok(Ok(())) => {},
+            Ok(Err(e)) => Self::push_error(&mut report, e),
+            Err(payload) => Self::push_error(&mut report, Error::from_panic(path, payload.as_ref())),
+        }
+        report.calculate_bytes_saved(original_size);
+        report
+    }
+
+
+
+
+
+    /// reads the samples in the format of the file and processes them
+    fn process_wav_file(&self, path: &Path, report: &mut FileReport) -> Result<()> {
+        // hound can't read 64 bit floats, so those go through our own reader.
+        if wav64::is_f64_wav(path) {
+            let (spec, samples) = wav64::read(path)?;
+            report.spec = Some(spec);
+            self.process_wav::<f64>(path, samples, spec, report);
+            return Ok(());
+        }
+
+        let mut reader = WavReader::open(path).map_err(|e| Error::from_hound_read(path, e))?;
+        let spec = reader.spec();
+        report.spec = Some(spec);
+        // auto cut needs all the samples at once, so those files are always read into memory.
+        if self.options.stream && self.options.auto_cut.is_none() {
+            return self.stream_wav_file(path, spec, report);
+        }
+
+        let bits = spec.bits_per_sample;
+        match spec.sample_format {
+            hound::SampleFormat::Int => {
+                match bits {
+                    // 8 bit wavs are unsigned around a 128 midpoint, hound shifts them to signed
+                    // values on read and back to unsigned on write, so the symmetric check works.
+                    8 => {
+                        self.process_wav::<i8>(path, read_samples(path, &mut reader)?, spec, report);
+                    },
+                    16 => {
+                        self.process_wav::<i16>(path, read_samples(path, &mut reader)?, spec, report);
+                    },
+                    24 => {
+                        self.process_wav::<i32>(path, read_samples(path, &mut reader)?, spec, report);
+                    },
+                    32 => {
+                        self.process_wav::<i32>(path, read_samples(path, &mut reader)?, spec, report);
+                    },
+                    _ => {
+                        return Err(Error::UnsupportedFormat { path: path.to_path_buf(), format: format!("{bits} bit integer samples") });
+                    }
+                }
+            },
+            hound::SampleFormat::Float => {
+                match bits {
+                    32 => {
+                        self.process_wav::<f32>(path, read_samples(path, &mut reader)?, spec, report);
+                    },
+                    _ => {
+                        return Err(Error::UnsupportedFormat { path: path.to_path_buf(), format: format!("{bits} bit floating point samples") });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+
+
+
+
+    /// checks if the current dir or file is a .wav file and processes.
+    /// **Returns** the report of the file if it was a .wav file.
+    pub fn check_file_for_wav(&self, entry: DirEntry) -> Option<FileReport> {
+        // println!("looking at path: {}", entry.path().display());
+        if let Some(file_type) = entry.file_type() {
+            if file_type.is_file() {
+                // println!("file name: {:?}", entry.file_name());
+                if let Some(name) = entry.file_name().to_str() {
+                    let name = name.to_lowercase();
+                    let name: Vec<&str> = name.split('.').rev().collect();
+                    if let Some(extention) = name.first() {
+                        if *extention == "wav" {
+                            return Some(self.setup_wav_processing(entry.path()));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+}
+
+#[test]
+fn nearest_zero_crossing() {
+    let channel: Vec<i16> = vec![5, 10, 8, 3, -2, -6, -9, -4, 1, 7];
+    // crossing between 3 and -2 picks -2, between -4 and 1 picks 1.
+    assert_eq!(WavProcessor::find_nearest_zero_crossing(&channel, 1, 5), 4);
+    assert_eq!(WavProcessor::find_nearest_zero_crossing(&channel, 7, 5), 8);
+    assert_eq!(WavProcessor::find_nearest_zero_crossing(&channel, 6, 1), 7);
+    assert_eq!(WavProcessor::find_nearest_zero_crossing(&channel, 1, 1), 1);
+    assert_eq!(WavProcessor::get_loudest_channel(&[vec![1_i16, -1], vec![0, -3], vec![2, 2]]), Some(1));
+}
+
+#[cfg(test)]
+fn test_processor(detection: Detection) -> WavProcessor {
+    WavProcessor::new(Options { db: -6.0, detection, ..Options::default() })
+}
+
+#[test]
+fn rms_detection() {
+    // a single spike in silence, and a quiet tone that crosses zero.
+    let mut channel = vec![0_f32; 100];
+    channel[10] = 1.0;
+    for (i, sample) in channel.iter_mut().enumerate().skip(50) {
+        *sample = if i % 2 == 0 { 0.8 } else { -0.8 };
+    }
+    channel[60] = 0.0;
+    let deviation = db_to_normalized_value(-6.0);
+
+    let peak = test_processor(Detection::Peak).get_loud_samples(&channel, deviation, 1000);
+    assert!(peak[10]);
+    assert!(!peak[60]);
+
+    let rms = test_processor(Detection::Rms { window_ms: 10.0 }).get_loud_samples(&channel, deviation, 1000);
+    assert!(!rms[10]);
+    assert!(rms[60]);
+    assert!(rms[55..95].iter().all(|l| *l));
+}
+
+#[test]
+fn gate_hysteresis() {
+    // hovers between the close (0.2) and open (0.5) thresholds after opening, then drops for good.
+    let channel: Vec<f32> = vec![0.3, 0.6, 0.3, 0.1, 0.3, 0.1, 0.1, 0.1, 0.3, 0.3];
+    let gated = test_processor(Detection::Peak).get_gated_samples(&channel, 0.5, 0.2, 2, 1000);
+    assert_eq!(gated, vec![false, true, true, true, true, false, false, false, false, false]);
+}
+
+#[test]
+fn noise_floor_threshold() {
+    // quiet hiss at 0.01 for most of the file with a loud part in the middle.
+    let mut samples = vec![0.01_f32; 1000];
+    for (i, sample) in samples.iter_mut().enumerate() {
+        if i % 2 == 1 { *sample = -*sample; }
+        if (400..500).contains(&i) { *sample *= 50.0; }
+    }
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+    let nf = NoiseFloor { above_db: 20.0, percentile: 10.0, window_ms: 10.0 };
+
+    let processor = WavProcessor::new(Options::default());
+
+    let (floor, threshold) = processor.get_noise_floor_threshold(samples.iter().copied(), &spec, &nf);
+    assert!((floor - 0.01).abs() < 1e-6);
+    assert!((threshold - 0.1).abs() < 1e-6);
+
+    // a block of digital silence doesn't count as the noise floor.
+    samples[600..1000].fill(0.0);
+    let (floor, threshold) = processor.get_noise_floor_threshold(samples.iter().copied(), &spec, &nf);
+    assert!((floor - 0.01).abs() < 1e-6);
+    assert!((threshold - 0.1).abs() < 1e-6);
+
+    // the '-db' threshold is only used if the file is nothing but silence, otherwise it's no limit either way.
+    let (floor, threshold) = processor.get_noise_floor_threshold([0.0_f32; 1000], &spec, &nf);
+    assert_eq!(floor, 0.0);
+    assert!((threshold - db_to_normalized_value(-60.0) as f64).abs() < 1e-9);
+    let loud = WavProcessor::new(Options { db: -6.0, ..Options::default() });
+    assert!((loud.get_noise_floor_threshold(samples.iter().copied(), &spec, &nf).1 - 0.1).abs() < 1e-6);
+}
+
+#[test]
+fn trim_in_memory() {
+    // stereo, the right channel is empty, the left one has silence at both ends and in the middle.
+    let mut samples = vec![0_i16; 2 * 3000];
+    for i in (100..700).chain(1200..1800) {
+        samples[i * 2] = if i % 2 == 0 { 10000 } else { -10000 };
+    }
+    let spec = hound::WavSpec { channels: 2, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut options = Options { db: -20.0, auto_cut: Some(AutoCut::default()), ..Options::default() };
+    options.trim.start = true;
+    let processor = WavProcessor::new(options);
+
+    let audio = processor.trim_samples(&samples, spec);
+    assert_eq!(audio.spec.channels, 1);
+    assert_eq!(audio.removed_channels, vec![1]);
+    assert_eq!(audio.original_samples, 3000);
+    assert_eq!(audio.start, 100);
+    assert_eq!(audio.channels[0].len(), 1700);
+    assert_eq!(audio.deviation, 3276);
+
+    assert_eq!(processor.find_cut_regions(&audio), Some(vec![(0, 600), (1099, 1699)]));
+}
+
+#[test]
+fn pre_and_post_roll() {
+    let tone = |range: std::ops::Range<usize>| {
+        let mut samples = vec![0_i16; 1000];
+        for i in range {
+            samples[i] = if i % 2 == 0 { 10000 } else { -10000 };
+        }
+        samples
+    };
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut options = Options { db: -20.0, ..Options::default() };
+    options.trim = crate::options::Trim { start: true, pre_roll_ms: 50.0, post_roll_ms: 30.0, ..Default::default() };
+    let processor = WavProcessor::new(options);
+
+    // 50 samples before the tone at 100..200 and 30 after it are kept.
+    let audio = processor.trim_samples(&tone(100..200), spec);
+    assert_eq!((audio.start, audio.channels[0].len()), (50, 180));
+    // the padding stops at both ends of the file.
+    let audio = processor.trim_samples(&tone(20..990), spec);
+    assert_eq!((audio.start, audio.channels[0].len()), (0, 1000));
+
+    let ac = AutoCut { min_silence_length_ms: 0.0, min_length_per_sample_ms: 0.0, ..AutoCut::default() };
+    let channels = [vec![0_i16; 1000]];
+    assert_eq!(processor.get_cut_regions(&mut vec![(300, 600)], &channels, 1000, &ac), Some(vec![(0, 330), (550, 999)]));
+    assert_eq!(processor.get_cut_regions(&mut vec![(20, 40), (975, 990)], &channels, 1000, &ac), Some(vec![(0, 50), (0, 999), (940, 999)]));
+
+    // with zero crossings the padded cut points move, the silences themselves have no crossing close by.
+    let mut options = Options { db: -20.0, ..Options::default() };
+    options.trim = crate::options::Trim { pre_roll_ms: 50.0, post_roll_ms: 30.0, zero_crossing_window_ms: Some(20.0), ..Default::default() };
+    let processor = WavProcessor::new(options);
+    let channels = [(0..1000).map(|i| if (345..560).contains(&i) { -1000_i16 } else { 1000 }).collect::<Vec<i16>>()];
+    assert_eq!(processor.get_cut_regions(&mut vec![(300, 600)], &channels, 1000, &ac), Some(vec![(0, 344), (559, 999)]));
+}
+
+#[test]
+fn cut_markers() {
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_markers_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("loop.wav");
+    let mut samples = vec![0_i16; 3000];
+    for i in (100..700).chain(1200..1800) {
+        samples[i] = if i % 2 == 0 { 10000 } else { -10000 };
+    }
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    crate::sample::write_with_hound(&path, spec, &samples).unwrap();
+
+    let mut options = Options { db: -20.0, auto_cut: Some(AutoCut { output: crate::options::CutOutput::Markers, ..AutoCut::default() }), ..Options::default() };
+    options.trim.start = true;
+    let report = WavProcessor::new(options).setup_wav_processing(&path);
+    assert!(report.is_ok());
+    assert_eq!(report.outputs, vec![dir.join("loop_stripped.wav")]);
+
+    let written = chunks::read_chunks(&report.outputs[0]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let markers = [
+        chunks::Marker { start: 0, len: 601, label: "loop_stripped-01".to_string() },
+        chunks::Marker { start: 1099, len: 601, label: "loop_stripped-02".to_string() },
+    ];
+    assert_eq!(written, chunks::marker_chunks(&markers));
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    fn snapshot(dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.push((path.clone(), Vec::new()));
+                snapshot(&path, files);
+            } else {
+                files.push((path.clone(), fs::read(&path).unwrap()));
+            }
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_dry_run_{}", std::process::id()));
+    fs::create_dir_all(dir.join("drums")).unwrap();
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut samples = vec![0_i16; 3000];
+    for i in (100..700).chain(1200..1800) {
+        samples[i] = if i % 2 == 0 { 10000 } else { -10000 };
+    }
+    crate::sample::write_with_hound(&dir.join("drums/loop.wav"), spec, &samples).unwrap();
+    crate::sample::write_with_hound(&dir.join("drums/empty.wav"), spec, &[0_i16; 100]).unwrap();
+    let mut before = Vec::new();
+    snapshot(&dir, &mut before);
+    before.sort();
+
+    let ac = AutoCut {
+        delete_original: true,
+        create_subdirectory: true,
+        output: crate::options::CutOutput::Both,
+        region_lists: vec![crate::RegionFormat::Csv],
+        instrument: crate::Instrument { sfz: true, dspreset: true, ..crate::Instrument::default() },
+        ..AutoCut::default()
+    };
+    let overwrite = Options { db: -20.0, dry_run: true, overwrite_input: true, delete_empty: true, backup: Some(dir.join("backups")), auto_cut: Some(ac), ..Options::default() };
+    let out_dir = Options { db: -20.0, dry_run: true, out_dir: Some(dir.join("out")), paths: vec![dir.clone()], backup: Some(dir.join("backups")), ..Options::default() };
+    for options in [overwrite, out_dir] {
+        let processor = WavProcessor::new(options);
+        assert!(processor.backup_dir().is_none());
+        for name in ["drums/loop.wav", "drums/empty.wav"] {
+            let report = processor.setup_wav_processing(&dir.join(name));
+            assert!(report.is_ok());
+            assert!(report.outputs.is_empty() && report.deleted.is_empty());
+        }
+    }
+
+    let mut after = Vec::new();
+    snapshot(&dir, &mut after);
+    after.sort();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(after, before);
+}
+
+#[test]
+fn markers_and_cuts_with_one_name() {
+    let dir = std::env::temp_dir().join(format!("wav_optimizer_marker_names_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("loop.wav");
+    let mut samples = vec![0_i16; 3000];
+    for i in (100..700).chain(1200..1800) {
+        samples[i] = if i % 2 == 0 { 10000 } else { -10000 };
+    }
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    crate::sample::write_with_hound(&path, spec, &samples).unwrap();
+
+    // the first cut starts where the file with the markers does.
+    let options = |template: &str| {
+        let mut options = Options { db: -20.0, naming: Naming { template: Some(template.to_string()), ..Naming::default() }, ..Options::default() };
+        options.auto_cut = Some(AutoCut { output: crate::options::CutOutput::Both, ..AutoCut::default() });
+        options
+    };
+    let report = WavProcessor::new(options("{name}_{start}")).setup_wav_processing(&path);
+    assert!(matches!(report.errors[..], [Error::Path { .. }]));
+    assert!(report.outputs.is_empty());
+
+    // the file with the markers isn't a cut, so it has no index.
+    let report = WavProcessor::new(options("{name}_{index}")).setup_wav_processing(&path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.outputs, vec![dir.join("loop_.wav"), dir.join("loop_01.wav"), dir.join("loop_02.wav")]);
+}
+
+#[test]
+fn truncated_file_is_reported() {
+    let dir = std::env::temp_dir().join("wav_optimizer_truncated");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("truncated.wav");
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    crate::sample::write_with_hound(&path, spec, &[1000_i16; 100]).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 51]).unwrap();
+
+    let report = WavProcessor::new(Options::default()).setup_wav_processing(&path);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!report.is_ok());
+    assert!(matches!(report.errors[0], Error::Decode { .. }));
+    assert!(report.outputs.is_empty());
+}
+
+#[cfg(test)]
+use crate::naming::Naming;
+
+#[test]
+fn output_paths() {
+    let processor = WavProcessor::new(Options::default());
+    let single = OutputInfo::default();
+    let cut = OutputInfo { slice: Some(0), ..OutputInfo::default() };
+    assert_eq!(processor.get_output_path(Path::new("./lib/kick.wav"), &single).unwrap(), Path::new("./lib/kick_stripped.wav"));
+
+    let mut options = Options { out_dir: Some(PathBuf::from("out")), paths: vec![PathBuf::from("lib"), PathBuf::from("lib/drums")], delete_empty: true, ..Options::default() };
+    options.auto_cut = Some(AutoCut { delete_original: true, ..AutoCut::default() });
+    let processor = WavProcessor::new(options);
+    assert!(!processor.options.delete_empty);
+    assert_eq!(processor.get_output_path(Path::new("lib/drums/kick.WAV"), &cut).unwrap(), Path::new("out/kick-01.wav"));
+    assert_eq!(processor.get_output_path(Path::new("lib/fx/a/boom.wav"), &single).unwrap(), Path::new("out/fx/a/boom.wav"));
+    assert_eq!(processor.get_output_path(Path::new("/elsewhere/boom.wav"), &single).unwrap(), Path::new("out/boom.wav"));
+
+    let processor = WavProcessor::new(Options { out_dir: Some(PathBuf::from("out")), paths: vec![PathBuf::from("./lib")], ..Options::default() });
+    assert_eq!(processor.get_output_path(Path::new("lib/fx/boom.wav"), &single).unwrap(), Path::new("out/fx/boom.wav"));
+    let processor = WavProcessor::new(Options { out_dir: Some(PathBuf::from("out")), paths: vec![PathBuf::from("lib")], ..Options::default() });
+    assert_eq!(processor.get_output_path(Path::new("./lib/fx/boom.wav"), &single).unwrap(), Path::new("out/fx/boom.wav"));
+
+    let mut options = Options { naming: Naming { template: Some("{note}/{name} {index}".to_string()), index_padding: 3, ..Naming::default() }, ..Options::default() };
+    options.auto_cut = Some(AutoCut::default());
+    let processor = WavProcessor::new(options);
+    let cut = OutputInfo { slice: Some(4), ..OutputInfo::default() };
+    assert_eq!(processor.get_output_path(Path::new("lib/kick.wav"), &cut).unwrap(), Path::new("lib/E4/kick 005.wav"));
+
+    // a template that renders the name of the input only overwrites it with '-o'.
+    let naming = || Naming { template: Some("{name}".to_string()), ..Naming::default() };
+    let processor = WavProcessor::new(Options { naming: naming(), ..Options::default() });
+    assert_eq!(processor.get_output_path(Path::new("./lib/kick.wav"), &single).unwrap(), Path::new("./lib/kick_stripped.wav"));
+    assert_eq!(processor.get_output_path(Path::new("lib/kick.WAV"), &single).unwrap(), Path::new("lib/kick_stripped.wav"));
+    let processor = WavProcessor::new(Options { naming: naming(), overwrite_input: true, ..Options::default() });
+    assert_eq!(processor.get_output_path(Path::new("lib/kick.wav"), &single).unwrap(), Path::new("lib/kick.wav"));
+
+    // cuts that would all get the same name are refused.
+    let mut options = Options { naming: Naming { template: Some("{name}_cut".to_string()), ..Naming::default() }, ..Options::default() };
+    options.auto_cut = Some(AutoCut::default());
+    let processor = WavProcessor::new(options);
+    let slice_output = |i: usize, _region: &(usize, usize)| OutputInfo { slice: Some(i), ..OutputInfo::default() };
+    assert!(matches!(processor.get_slice_paths(&[(0, 10), (20, 30)], &slice_output, Path::new("lib/y.wav")), Err(Error::Path { .. })));
+    assert_eq!(processor.get_slice_paths(&[(0, 10)], &slice_output, Path::new("lib/y.wav")).unwrap(), vec![PathBuf::from("lib/y_cut.wav")]);
+}
@@ -0,0 +1,107 @@
+//! Region lists of the auto cuts, for editors that show the slices next to the file.
+
+use std::fmt::Write;
+
+/// The file formats a region list can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionFormat {
+    /// `index,start_sample,end_sample,start_seconds,end_seconds,file` with a header line.
+    Csv,
+    /// an Audacity label track, `start<TAB>end<TAB>label` in seconds.
+    Audacity,
+    /// the CSV the REAPER region/marker manager imports.
+    Reaper,
+}
+
+impl RegionFormat {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "csv" => Some(RegionFormat::Csv),
+            "audacity" => Some(RegionFormat::Audacity),
+            "reaper" => Some(RegionFormat::Reaper),
+            _ => None,
+        }
+    }
+
+    /// **Returns** what gets added to the name of the file the list belongs to.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            RegionFormat::Csv => ".regions.csv",
+            RegionFormat::Audacity => ".labels.txt",
+            RegionFormat::Reaper => ".reaper.csv",
+        }
+    }
+
+    /// **Returns** the region list in this format.
+    pub(crate) fn render(self, regions: &[Region], sample_rate: u32) -> String {
+        let seconds = |frames: usize| frames as f64 / sample_rate.max(1) as f64;
+        let mut list = String::new();
+        match self {
+            RegionFormat::Csv => {
+                list.push_str("index,start_sample,end_sample,start_seconds,end_seconds,file\n");
+                for region in regions {
+                    writeln!(list, "{},{},{},{:.6},{:.6},{}", region.index, region.start, region.end, seconds(region.start), seconds(region.end), csv_field(&region.file)).unwrap();
+                }
+            },
+            RegionFormat::Audacity => {
+                for region in regions {
+                    let label = region.file.rsplit_once('.').map_or(region.file.as_str(), |(stem, _)| stem);
+                    writeln!(list, "{:.6}\t{:.6}\t{}", seconds(region.start), seconds(region.end), label).unwrap();
+                }
+            },
+            RegionFormat::Reaper => {
+                list.push_str("#,Name,Start,End,Length\n");
+                for region in regions {
+                    let name = region.file.rsplit_once('.').map_or(region.file.as_str(), |(stem, _)| stem);
+                    let (start, end) = (seconds(region.start), seconds(region.end));
+                    writeln!(list, "R{},{},{},{},{}", region.index, csv_field(name), reaper_time(start), reaper_time(end), reaper_time(end - start)).unwrap();
+                }
+            },
+        }
+        list
+    }
+}
+
+/// A single cut, in frames of the original file.
+#[derive(Debug, Clone)]
+pub(crate) struct Region {
+    /// the number the cut has in its file name.
+    pub index: usize,
+    pub start: usize,
+    /// the frame after the last one of the cut.
+    pub end: usize,
+    /// the file name the cut is written to, or would be.
+    pub file: String,
+}
+
+/// quotes a field that contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// `minutes:seconds.milliseconds`, the way REAPER writes times.
+fn reaper_time(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", millis / 60000, millis / 1000 % 60, millis % 1000)
+}
+
+#[test]
+fn region_lists() {
+    let regions = [
+        Region { index: 1, start: 100, end: 701, file: "kick-01.wav".to_string() },
+        Region { index: 2, start: 61199, end: 62000, file: "kick, soft-02.wav".to_string() },
+    ];
+    assert_eq!(
+        RegionFormat::Csv.render(&regions, 1000),
+        "index,start_sample,end_sample,start_seconds,end_seconds,file\n1,100,701,0.100000,0.701000,kick-01.wav\n2,61199,62000,61.199000,62.000000,\"kick, soft-02.wav\"\n"
+    );
+    assert_eq!(RegionFormat::Audacity.render(&regions, 1000), "0.100000\t0.701000\tkick-01\n61.199000\t62.000000\tkick, soft-02\n");
+    assert_eq!(
+        RegionFormat::Reaper.render(&regions, 1000),
+        "#,Name,Start,End,Length\nR1,kick-01,0:00.100,0:00.701,0:00.601\nR2,\"kick, soft-02\",1:01.199,1:02.000,0:00.801\n"
+    );
+}
@@ -0,0 +1,123 @@
+//! Machine readable report of everything that happened to each processed file.
+
+use std::{fmt::{self, Write}, fs, path::{Path, PathBuf}};
+
+use crate::error::Error;
+
+/// What happened to a single input file.
+#[derive(Debug, Default)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub spec: Option<hound::WavSpec>,
+    /// length of the input in samples per channel.
+    pub original_samples: usize,
+    /// length after trimming in samples per channel.
+    pub new_samples: usize,
+    pub removed_channels: Vec<usize>,
+    /// the silences the file got cut at, in samples of the trimmed audio (inclusive).
+    pub silence_ranges: Vec<(usize, usize)>,
+    pub outputs: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    /// bytes on disk before minus after, negative if the outputs take up more space.
+    pub bytes_saved: i64,
+    pub errors: Vec<Error>,
+    /// everything that got printed about the file, so files processed at the same time don't get mixed up.
+    pub log: String,
+}
+
+impl FileReport {
+    pub fn new(path: &Path) -> Self {
+        FileReport {
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// adds a line to the log of the file.
+    pub(crate) fn log(&mut self, line: fmt::Arguments) {
+        self.log.write_fmt(line).unwrap();
+        self.log.push('\n');
+    }
+
+    /// **Returns** true if nothing went wrong with the file.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// fills in `bytes_saved` from the current state on disk, `original_size` being the size of the input beforehand.
+    pub fn calculate_bytes_saved(&mut self, original_size: u64) {
+        let file_size = |path: &Path| fs::metadata(path).map_or(0, |m| m.len());
+        let mut after: u64 = self.outputs.iter().map(|p| file_size(p)).sum();
+        if !self.outputs.contains(&self.path) {
+            after += file_size(&self.path);
+        }
+        self.bytes_saved = original_size as i64 - after as i64;
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        write!(json, "\"path\":{}", json_string(&self.path.to_string_lossy())).unwrap();
+        match &self.spec {
+            Some(spec) => write!(json,
+                ",\"spec\":{{\"channels\":{},\"sample_rate\":{},\"bits_per_sample\":{},\"sample_format\":\"{:?}\"}}",
+                spec.channels, spec.sample_rate, spec.bits_per_sample, spec.sample_format
+            ).unwrap(),
+            None => json.push_str(",\"spec\":null"),
+        }
+        write!(json, ",\"original_samples\":{},\"new_samples\":{}", self.original_samples, self.new_samples).unwrap();
+        write!(json, ",\"removed_channels\":[{}]", join(self.removed_channels.iter().map(|c| c.to_string()))).unwrap();
+        write!(json, ",\"silence_ranges\":[{}]", join(self.silence_ranges.iter().map(|r| format!("[{},{}]", r.0, r.1)))).unwrap();
+        write!(json, ",\"outputs\":[{}]", join(self.outputs.iter().map(|p| json_string(&p.to_string_lossy())))).unwrap();
+        write!(json, ",\"deleted\":[{}]", join(self.deleted.iter().map(|p| json_string(&p.to_string_lossy())))).unwrap();
+        write!(json, ",\"bytes_saved\":{}", self.bytes_saved).unwrap();
+        write!(json, ",\"errors\":[{}]", join(self.errors.iter().map(|e| json_string(&e.to_string())))).unwrap();
+        json.push('}');
+        json
+    }
+}
+
+/// writes all file reports as one JSON document.
+pub fn write_report(path: &Path, reports: &[FileReport]) -> std::io::Result<()> {
+    let files = join(reports.iter().map(|r| format!("\n    {}", r.to_json())));
+    fs::write(path, format!("{{\n  \"files\": [{}\n  ]\n}}\n", files))
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<String>>().join(",")
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[test]
+fn report_json() {
+    let mut report = FileReport::new(Path::new("dir/a \"b\".wav"));
+    report.spec = Some(hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int });
+    report.original_samples = 100;
+    report.new_samples = 50;
+    report.removed_channels = vec![1];
+    report.silence_ranges = vec![(10, 20), (30, 40)];
+    report.errors.push(Error::Path { path: PathBuf::from("x"), message: "line\nbreak".to_string() });
+
+    assert_eq!(report.to_json(), concat!(
+        "{\"path\":\"dir/a \\\"b\\\".wav\",",
+        "\"spec\":{\"channels\":2,\"sample_rate\":44100,\"bits_per_sample\":16,\"sample_format\":\"Int\"},",
+        "\"original_samples\":100,\"new_samples\":50,\"removed_channels\":[1],\"silence_ranges\":[[10,20],[30,40]],",
+        "\"outputs\":[],\"deleted\":[],\"bytes_saved\":0,\"errors\":[\"invalid path \\\"x\\\": line\\nbreak\"]}"
+    ));
+}
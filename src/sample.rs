@@ -0,0 +1,123 @@
+//! The sample types a wav file can be processed in.
+
+use std::{io, path::Path};
+
+use hound::WavReader;
+
+use crate::{error::{Error, Result}, wav64};
+
+/// A sample type that can be trimmed and written back out in its original format.
+pub trait WavSample: Copy + Default + PartialOrd + std::ops::Neg<Output = Self> + std::fmt::Debug {
+    /// writes the interleaved samples into a new wav file at `path`.
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()>;
+
+    /// the raw sample value, not normalized.
+    fn to_f64(self) -> f64;
+
+    /// converts a raw value back, rounding and clamping integers.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl WavSample for i8 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.round() as i8
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()> {
+        write_with_hound(path, spec, samples)
+    }
+}
+
+impl WavSample for i16 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.round() as i16
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()> {
+        write_with_hound(path, spec, samples)
+    }
+}
+
+impl WavSample for i32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.round() as i32
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()> {
+        write_with_hound(path, spec, samples)
+    }
+}
+
+impl WavSample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()> {
+        write_with_hound(path, spec, samples)
+    }
+}
+
+impl WavSample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn write_wav(path: &Path, spec: hound::WavSpec, samples: &[Self]) -> Result<()> {
+        wav64::write(path, spec, samples)
+    }
+}
+
+pub(crate) fn write_with_hound<S: hound::Sample + Copy>(path: &Path, spec: hound::WavSpec, samples: &[S]) -> Result<()> {
+    let mut writer = hound::WavWriter::create(path, spec).map_err(|e| match e {
+        hound::Error::IoError(e) => Error::io(path, e),
+        e => Error::encode(path, e),
+    })?;
+    for sample in samples {
+        writer.write_sample(*sample).map_err(|e| Error::encode(path, e))?;
+    }
+    writer.finalize().map_err(|e| Error::encode(path, e))
+}
+
+/// reads all interleaved samples from a hound reader, failing on the first one that can't be read.
+pub(crate) fn read_samples<T: hound::Sample, R: io::Read>(path: &Path, reader: &mut WavReader<R>) -> Result<Vec<T>> {
+    reader.samples::<T>().map(|s| s.map_err(|e| Error::decode(path, e))).collect()
+}
+
+#[test]
+fn unsigned_8_bit_roundtrip() {
+    let path = std::env::temp_dir().join("wav_optimizer_8_bit_roundtrip.wav");
+    let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 8, sample_format: hound::SampleFormat::Int };
+    // raw unsigned bytes, 128 being silence.
+    let raw: Vec<u8> = vec![128, 129, 255, 0, 127, 128];
+    let samples: Vec<i8> = raw.iter().map(|b| (*b as i16 - 128) as i8).collect();
+
+    i8::write_wav(&path, spec, &samples).unwrap();
+    let mut reader = WavReader::open(&path).unwrap();
+    let read: Vec<i8> = read_samples(&path, &mut reader).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read, samples);
+    assert_eq!(&bytes[bytes.len() - raw.len()..], raw.as_slice());
+}
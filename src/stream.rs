@@ -0,0 +1,338 @@
+//! Bounded memory processing of large files.
+//!
+//! Instead of loading every sample, the file is read once to find the loud parts of each channel and
+//! a second time to copy the kept frames straight into the new file.
+
+use std::{collections::VecDeque, fs, io, path::Path};
+
+use hound::WavReader;
+
+use crate::{
+    atomic::write_atomically,
+    error::{Error, Result},
+    get_full_scale, normalized_value_to_db,
+    naming::OutputInfo,
+    options::Detection,
+    processor::WavProcessor,
+    report::FileReport,
+    sample::WavSample,
+};
+
+/// What the first pass found out about a single channel.
+#[derive(Debug, Default, Clone)]
+struct ChannelStats {
+    first_loud: Option<usize>,
+    last_loud: usize,
+    /// the sum of squares, to find the loudest channel.
+    energy: f64,
+}
+
+impl ChannelStats {
+    fn record(&mut self, i: usize, is_loud: bool) {
+        if is_loud {
+            self.last_loud = i;
+            self.first_loud.get_or_insert(i);
+        }
+    }
+}
+
+/// Measures the level of a channel one sample at a time, giving the same levels as `get_levels` does for a whole channel.
+struct LevelMeter {
+    /// `None` for peak detection.
+    half_window: Option<usize>,
+    squares: VecDeque<f64>,
+    sum: f64,
+    /// the index of the next level to hand out.
+    next: usize,
+    pushed: usize,
+}
+
+impl LevelMeter {
+    fn new(detection: Detection, sample_rate: u32) -> Self {
+        let half_window = match detection {
+            Detection::Peak => None,
+            Detection::Rms { window_ms } => Some((WavProcessor::get_sample_len_from_ms(&window_ms, &sample_rate) as usize / 2).max(1)),
+        };
+        LevelMeter { half_window, squares: VecDeque::new(), sum: 0.0, next: 0, pushed: 0 }
+    }
+
+    /// adds the next sample and calls `on_level` with every level that is known by now.
+    /// An RMS level is only known once the samples in the second half of its window came in.
+    fn push(&mut self, sample: f64, mut on_level: impl FnMut(usize, f64)) {
+        let Some(half_window) = self.half_window else {
+            on_level(self.next, sample.abs());
+            self.next += 1;
+            return;
+        };
+
+        self.squares.push_back(sample * sample);
+        self.sum += sample * sample;
+        self.pushed += 1;
+        if self.squares.len() > 2 * half_window + 1 {
+            self.sum -= self.squares.pop_front().unwrap_or(0.0);
+        }
+        if self.pushed > half_window {
+            on_level(self.next, (self.sum.max(0.0) / self.squares.len() as f64).sqrt());
+            self.next += 1;
+        }
+    }
+
+    /// calls `on_level` with the levels at the end of the channel, which were still waiting on samples that never came.
+    fn finish(&mut self, mut on_level: impl FnMut(usize, f64)) {
+        let Some(half_window) = self.half_window else { return };
+        while self.next < self.pushed {
+            // the window of the last levels only shrinks at the front
+            while self.pushed - self.squares.len() + half_window < self.next {
+                self.sum -= self.squares.pop_front().unwrap_or(0.0);
+            }
+            on_level(self.next, (self.sum.max(0.0) / self.squares.len() as f64).sqrt());
+            self.next += 1;
+        }
+    }
+}
+
+impl WavProcessor {
+    /// trims a file like `process_wav` does, without ever holding more than a few samples in memory.
+    pub(crate) fn stream_wav_file(&self, path: &Path, spec: hound::WavSpec, report: &mut FileReport) -> Result<()> {
+        let bits = spec.bits_per_sample;
+        match (spec.sample_format, bits) {
+            (hound::SampleFormat::Int, 8) => self.stream_wav::<i8>(path, spec, report),
+            (hound::SampleFormat::Int, 16) => self.stream_wav::<i16>(path, spec, report),
+            (hound::SampleFormat::Int, 24 | 32) => self.stream_wav::<i32>(path, spec, report),
+            (hound::SampleFormat::Float, 32) => self.stream_wav::<f32>(path, spec, report),
+            (hound::SampleFormat::Int, _) => Err(Error::UnsupportedFormat { path: path.to_path_buf(), format: format!("{bits} bit integer samples") }),
+            (hound::SampleFormat::Float, _) => Err(Error::UnsupportedFormat { path: path.to_path_buf(), format: format!("{bits} bit floating point samples") }),
+        }
+    }
+
+
+
+
+
+    fn stream_wav<T: WavSample + hound::Sample>(&self, path: &Path, spec: hound::WavSpec, report: &mut FileReport) -> Result<()> {
+        let deviation: T = match &self.options.noise_floor {
+            Some(nf) => {
+                let mut reader = open(path)?;
+                let mut error = None;
+                let samples = reader.samples::<T>().map_while(|s| s.map_err(|e| error = Some(Error::decode(path, e))).ok());
+                let (floor, threshold) = self.get_noise_floor_threshold(samples, &spec, nf);
+                if let Some(e) = error {
+                    return Err(e);
+                }
+                let full_scale = get_full_scale(&spec);
+                report.log(format_args!("\tnoise floor: {:.1} dB, threshold: {:.1} dB", normalized_value_to_db(floor / full_scale), normalized_value_to_db(threshold / full_scale)));
+                T::from_f64(threshold)
+            },
+            None => self.get_deviation(&spec),
+        };
+
+        let (stats, frames) = self.get_channel_stats::<T>(path, &spec, deviation)?;
+        let kept: Vec<bool> = stats.iter().map(|stat| stat.last_loud != 0).collect();
+        let removed_channels: Vec<usize> = kept.iter().enumerate().filter(|(_i, kept)| !**kept).map(|(i, _kept)| i).collect();
+        let has_kept = kept.contains(&true);
+
+        let pre_roll = Self::get_sample_len_from_ms(&self.options.trim.pre_roll_ms, &spec.sample_rate) as usize;
+        let post_roll = Self::get_sample_len_from_ms(&self.options.trim.post_roll_ms, &spec.sample_rate) as usize;
+
+        // the kept channel with the most energy decides where the zero crossings are
+        let loudest = stats.iter().enumerate()
+            .filter(|(i, _stat)| kept[*i])
+            .max_by(|a, b| a.1.energy.total_cmp(&b.1.energy))
+            .map(|(i, _stat)| i);
+        let zero_crossing = self.options.trim.zero_crossing_window_ms
+            .map(|ms| Self::get_sample_len_from_ms(&ms, &spec.sample_rate) as usize)
+            .zip(loudest);
+
+        let mut end = frames.saturating_sub(1);
+        if self.options.trim.end && has_kept {
+            end = (stats.iter().map(|stat| stat.last_loud).max().unwrap_or(0) + post_roll).min(end);
+            if let Some((window, loudest)) = zero_crossing {
+                end = snap_to_zero_crossing::<T>(path, &spec, loudest, end, window, frames)?;
+            }
+        }
+
+        let mut start = 0_usize;
+        if self.options.trim.start && has_kept {
+            start = stats.iter().enumerate()
+                .filter(|(i, _stat)| kept[*i])
+                .filter_map(|(_i, stat)| stat.first_loud)
+                .min().unwrap_or(0).saturating_sub(pre_roll);
+            if let Some((window, loudest)) = zero_crossing {
+                start = snap_to_zero_crossing::<T>(path, &spec, loudest, start, window, end + 1)?;
+            }
+        }
+
+        let new_len = if has_kept { end + 1 - start } else { 0 };
+        report.original_samples = frames;
+        report.new_samples = new_len;
+        report.removed_channels = removed_channels.clone();
+        if self.options.dry_run {
+            report.log(format_args!("\toriginal length: {} samples, trimmed length: {} samples (starting at sample {}), channels to drop: {:?}", frames, new_len, start, removed_channels));
+        }
+
+        if !has_kept {
+            // nothing to copy, this deletes the file or leaves it be just like a file that was read into memory.
+            self.save_or_print_new_wav::<T>(&[], &mut spec.clone(), path, 0, report);
+            return Ok(());
+        }
+
+        let output = OutputInfo { slice: None, start, len: new_len, channels: kept.len() - removed_channels.len(), sample_rate: spec.sample_rate };
+        let out_path = self.get_output_path(path, &output)?;
+        if self.options.dry_run {
+            report.log(format_args!("\twould write {} samples in {} channels to {:?}", new_len, kept.len() - removed_channels.len(), out_path));
+            return Ok(());
+        }
+
+        if let Err(e) = self.create_output_dir(&out_path).and_then(|_| self.copy_frames::<T>(path, &out_path, &spec, &kept, start, new_len)) {
+            Self::push_error(report, e);
+            return Ok(());
+        }
+        report.outputs.push(out_path);
+        Ok(())
+    }
+
+
+
+
+
+    /// the first pass, finding the loud parts of every channel.
+    /// **Returns** the stats of every channel and the length of the file in frames.
+    fn get_channel_stats<T: WavSample + hound::Sample>(&self, path: &Path, spec: &hound::WavSpec, deviation: T) -> Result<(Vec<ChannelStats>, usize)> {
+        let num_channels = spec.channels as usize;
+        let threshold = deviation.to_f64().abs();
+        let mut stats = vec![ChannelStats::default(); num_channels];
+        let mut meters: Vec<LevelMeter> = (0..num_channels).map(|_| LevelMeter::new(self.options.detection, spec.sample_rate)).collect();
+
+        let mut reader = open(path)?;
+        let mut samples = 0_usize;
+        for (i, sample) in reader.samples::<T>().enumerate() {
+            let sample = sample.map_err(|e| Error::decode(path, e))?.to_f64();
+            let channel = i % num_channels;
+            let stat = &mut stats[channel];
+            stat.energy += sample * sample;
+            meters[channel].push(sample, |i, level| stat.record(i, level > threshold));
+            samples += 1;
+        }
+        for (meter, stat) in meters.iter_mut().zip(stats.iter_mut()) {
+            meter.finish(|i, level| stat.record(i, level > threshold));
+        }
+
+        Ok((stats, samples / num_channels))
+    }
+
+
+
+
+
+    /// the second pass, copying `len` frames from `start` on of the kept channels into `out_path`.
+    /// The frames are written into a temporary file first, so the input can be overwritten while it's being read.
+    fn copy_frames<T: WavSample + hound::Sample>(&self, path: &Path, out_path: &Path, spec: &hound::WavSpec, kept: &[bool], start: usize, len: usize) -> Result<()> {
+        write_atomically(out_path, self.journal.as_ref(), |temp_path| {
+            let mut reader = open(path)?;
+            reader.seek(start as u32).map_err(|e| Error::decode(path, e))?;
+
+            let new_spec = hound::WavSpec { channels: kept.iter().filter(|k| **k).count() as u16, ..*spec };
+            let mut writer = hound::WavWriter::create(temp_path, new_spec).map_err(|e| match e {
+                hound::Error::IoError(e) => Error::io(temp_path, e),
+                e => Error::encode(temp_path, e),
+            })?;
+
+            let fade = &self.options.fade;
+            let has_fade = fade.in_ms > 0.0 || fade.out_ms > 0.0;
+            let num_channels = kept.len();
+            let mut gain = 1.0;
+            for (i, sample) in reader.samples::<T>().take(len * num_channels).enumerate() {
+                let sample = sample.map_err(|e| Error::decode(path, e))?;
+                let channel = i % num_channels;
+                if channel == 0 && has_fade {
+                    gain = fade.gain_at(i / num_channels, len, spec.sample_rate);
+                }
+                if !kept[channel] {
+                    continue;
+                }
+                let sample = if gain < 1.0 { T::from_f64(sample.to_f64() * gain) } else { sample };
+                writer.write_sample(sample).map_err(|e| Error::encode(temp_path, e))?;
+            }
+            writer.finalize().map_err(|e| Error::encode(temp_path, e))?;
+            self.copy_chunks(path, temp_path, start, len, &[])
+        })
+    }
+}
+
+fn open(path: &Path) -> Result<WavReader<io::BufReader<fs::File>>> {
+    WavReader::open(path).map_err(|e| Error::from_hound_read(path, e))
+}
+
+/// moves `index` to the nearest zero crossing of `channel`, only reading the samples around it.
+/// `len` is the amount of frames the search is limited to.
+fn snap_to_zero_crossing<T: WavSample + hound::Sample>(path: &Path, spec: &hound::WavSpec, channel: usize, index: usize, window: usize, len: usize) -> Result<usize> {
+    let num_channels = spec.channels as usize;
+    let from = index.saturating_sub(window);
+    let frames = (index + window + 2).min(len).saturating_sub(from);
+
+    let mut reader = open(path)?;
+    reader.seek(from as u32).map_err(|e| Error::decode(path, e))?;
+    let samples: Vec<T> = reader.samples::<T>()
+        .take(frames * num_channels)
+        .skip(channel)
+        .step_by(num_channels)
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| Error::decode(path, e))?;
+
+    Ok(from + WavProcessor::find_nearest_zero_crossing(&samples, index - from, window))
+}
+
+#[test]
+fn level_meter_matches_get_levels() {
+    let channel: Vec<f32> = (0..50).map(|i| ((i * 7 % 13) as f32 - 6.0) / 6.0).collect();
+    for detection in [Detection::Peak, Detection::Rms { window_ms: 6.0 }] {
+        let processor = WavProcessor::new(crate::Options { detection, ..Default::default() });
+        let expected = processor.get_levels(&channel, 1000);
+
+        let mut levels = vec![f64::NAN; channel.len()];
+        let mut meter = LevelMeter::new(detection, 1000);
+        for sample in &channel {
+            meter.push(*sample as f64, |i, level| levels[i] = level);
+        }
+        meter.finish(|i, level| levels[i] = level);
+
+        for (level, expected) in levels.iter().zip(&expected) {
+            assert!((level - expected).abs() < 1e-9, "{:?}: {} != {}", detection, level, expected);
+        }
+    }
+}
+
+#[test]
+fn stream_matches_in_memory() {
+    let dir = std::env::temp_dir().join("wav_optimizer_stream");
+    fs::create_dir_all(&dir).unwrap();
+    let spec = hound::WavSpec { channels: 3, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    // the middle channel stays empty, the others have silence at both ends.
+    let mut samples = vec![0_i16; 3 * 2000];
+    for frame in 300..1500 {
+        samples[frame * 3] = if frame % 3 == 0 { 8000 } else { -5000 };
+        if frame < 900 { samples[frame * 3 + 2] = 2000; }
+    }
+
+    let mut outputs = Vec::new();
+    for stream in [false, true] {
+        let path = dir.join(format!("stream_{stream}.wav"));
+        crate::sample::write_with_hound(&path, spec, &samples).unwrap();
+        let mut options = crate::Options { db: -30.0, stream, detection: Detection::Rms { window_ms: 10.0 }, ..Default::default() };
+        options.trim.start = true;
+        options.trim.pre_roll_ms = 5.0;
+        options.trim.post_roll_ms = 5.0;
+        options.trim.zero_crossing_window_ms = Some(3.0);
+        options.fade.in_ms = 4.0;
+
+        let report = WavProcessor::new(options).setup_wav_processing(&path);
+        assert!(report.is_ok());
+        assert_eq!(report.removed_channels, vec![1]);
+        let mut reader = WavReader::open(&report.outputs[0]).unwrap();
+        outputs.push((reader.spec(), reader.samples::<i16>().map(|s| s.unwrap()).collect::<Vec<i16>>()));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(outputs[0].0.channels, 2);
+    assert_eq!(outputs[0], outputs[1]);
+}
@@ -0,0 +1,190 @@
+//! Reading and writing of 64 bit IEEE float wav files.
+//!
+//! hound only understands 32 bit floats, so this is a small hand rolled RIFF parser that covers
+//! the `WAVE_FORMAT_IEEE_FLOAT` and `WAVE_FORMAT_EXTENSIBLE` (float subformat) headers.
+
+use std::{fs::File, io::{BufReader, BufWriter, Read, Seek, Write}, path::Path};
+
+use crate::error::{Error, Result};
+
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// Returns true if the file at `path` is a wav file containing 64 bit float samples.
+pub fn is_f64_wav(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else { return false };
+    let mut reader = BufReader::new(file);
+    matches!(read_header(&mut reader), Ok(spec) if is_f64_spec(&spec))
+}
+
+fn is_f64_spec(spec: &hound::WavSpec) -> bool {
+    spec.sample_format == hound::SampleFormat::Float && spec.bits_per_sample == 64
+}
+
+/// Reads all interleaved samples of a 64 bit float wav file.
+pub fn read(path: &Path) -> Result<(hound::WavSpec, Vec<f64>)> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let file_len = file.metadata().map_err(|e| Error::io(path, e))?.len();
+    let mut reader = BufReader::new(file);
+    let spec = read_header(&mut reader).map_err(|e| Error::decode(path, e))?;
+    if !is_f64_spec(&spec) {
+        return Err(Error::decode(path, "not a 64 bit float wav file"));
+    }
+
+    let data_len = seek_chunk(&mut reader, b"data").map_err(|e| Error::decode(path, e))?;
+    // a broken header could ask for far more than the file holds, which must not end up as an allocation.
+    let data_start = reader.stream_position().map_err(|e| Error::io(path, e))?;
+    if data_len as u64 > file_len.saturating_sub(data_start) {
+        return Err(Error::decode(path, format!("the data chunk is {} bytes, but only {} are left in the file", data_len, file_len.saturating_sub(data_start))));
+    }
+    // like hound, a chunk that ends partway through a frame is refused, the channels would end up with different lengths.
+    let frame_len = spec.channels as u32 * 8;
+    if data_len % frame_len != 0 {
+        return Err(Error::decode(path, format!("invalid data chunk length, {} bytes isn't a whole number of {} byte frames", data_len, frame_len)));
+    }
+    let mut data = vec![0_u8; data_len as usize];
+    reader.read_exact(&mut data).map_err(|e| Error::decode(path, e))?;
+
+    let samples = data.chunks_exact(8)
+        .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect();
+    Ok((spec, samples))
+}
+
+/// Writes interleaved samples into a new 64 bit float wav file.
+pub fn write(path: &Path, spec: hound::WavSpec, samples: &[f64]) -> Result<()> {
+    // the sizes in the header are 32 bit, including the RIFF size that adds the header to the data.
+    let data_len = u32::try_from(samples.len() * 8).ok()
+        .filter(|len| len.checked_add(4 + 26 + 12 + 8).is_some())
+        .ok_or_else(|| Error::encode(path, "the audio is larger than 4 GB"))?;
+    let file = File::create(path).map_err(|e| Error::io(path, e))?;
+    let mut writer = BufWriter::new(file);
+
+    let block_align = spec.channels as u32 * 8;
+    let frames = if spec.channels > 0 { samples.len() as u32 / spec.channels as u32 } else { 0 };
+
+    let mut header: Vec<u8> = Vec::with_capacity(58);
+    header.extend_from_slice(b"RIFF");
+    // WAVE + fmt (8 + 18) + fact (8 + 4) + data (8 + len)
+    header.extend_from_slice(&(4 + 26 + 12 + 8 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&18_u32.to_le_bytes());
+    header.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+    header.extend_from_slice(&spec.channels.to_le_bytes());
+    header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(spec.sample_rate * block_align).to_le_bytes());
+    header.extend_from_slice(&(block_align as u16).to_le_bytes());
+    header.extend_from_slice(&64_u16.to_le_bytes());
+    header.extend_from_slice(&0_u16.to_le_bytes()); // cbSize
+
+    // non PCM formats should have a fact chunk with the amount of frames.
+    header.extend_from_slice(b"fact");
+    header.extend_from_slice(&4_u32.to_le_bytes());
+    header.extend_from_slice(&frames.to_le_bytes());
+
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    writer.write_all(&header).map_err(|e| Error::encode(path, e))?;
+
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes()).map_err(|e| Error::encode(path, e))?;
+    }
+    writer.flush().map_err(|e| Error::encode(path, e))
+}
+
+/// Reads the RIFF header and the fmt chunk, leaving the reader right after the fmt chunk.
+fn read_header<R: Read>(reader: &mut R) -> std::result::Result<hound::WavSpec, String> {
+    let mut riff = [0_u8; 12];
+    reader.read_exact(&mut riff).map_err(|e| e.to_string())?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err("not a RIFF WAVE file".to_string());
+    }
+
+    let fmt_len = seek_chunk(reader, b"fmt ")?;
+    if fmt_len < 16 {
+        return Err("fmt chunk is too small".to_string());
+    }
+    let mut fmt = vec![0_u8; fmt_len as usize + (fmt_len as usize & 1)];
+    reader.read_exact(&mut fmt).map_err(|e| e.to_string())?;
+
+    let format_tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+    let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+    if channels == 0 {
+        return Err("file contains zero channels".to_string());
+    }
+
+    let sample_format = match format_tag {
+        WAVE_FORMAT_IEEE_FLOAT => hound::SampleFormat::Float,
+        // the first two bytes of the subformat GUID hold the actual format tag.
+        WAVE_FORMAT_EXTENSIBLE if fmt_len >= 40 && u16::from_le_bytes([fmt[24], fmt[25]]) == WAVE_FORMAT_IEEE_FLOAT => hound::SampleFormat::Float,
+        _ => hound::SampleFormat::Int,
+    };
+
+    Ok(hound::WavSpec { channels, sample_rate, bits_per_sample, sample_format })
+}
+
+/// Skips chunks until one with the given id is found and returns its length.
+fn seek_chunk<R: Read>(reader: &mut R, id: &[u8; 4]) -> std::result::Result<u32, String> {
+    loop {
+        let mut chunk_header = [0_u8; 8];
+        reader.read_exact(&mut chunk_header).map_err(|_| format!("missing {:?} chunk", String::from_utf8_lossy(id)))?;
+        let len = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
+        if &chunk_header[0..4] == id {
+            return Ok(len);
+        }
+
+        // chunks are padded to an even amount of bytes.
+        let skip = len as u64 + (len as u64 & 1);
+        std::io::copy(&mut reader.by_ref().take(skip), &mut std::io::sink()).map_err(|e| e.to_string())?;
+    }
+}
+
+#[test]
+fn write_read_roundtrip() {
+    let path = std::env::temp_dir().join("wav_optimizer_wav64_roundtrip.wav");
+    let spec = hound::WavSpec { channels: 2, sample_rate: 48000, bits_per_sample: 64, sample_format: hound::SampleFormat::Float };
+    let samples = vec![0.0, -0.5, 0.25, 1.0, -1.0, 0.125];
+
+    write(&path, spec, &samples).unwrap();
+    assert!(is_f64_wav(&path));
+    let (read_spec, read_samples) = read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read_spec, spec);
+    assert_eq!(read_samples, samples);
+}
+
+#[test]
+fn data_len_past_the_end() {
+    let path = std::env::temp_dir().join("wav_optimizer_wav64_data_len.wav");
+    let spec = hound::WavSpec { channels: 1, sample_rate: 48000, bits_per_sample: 64, sample_format: hound::SampleFormat::Float };
+    write(&path, spec, &[0.5, -0.5]).unwrap();
+    // the data length sits right before the samples.
+    let mut bytes = std::fs::read(&path).unwrap();
+    let len_at = bytes.len() - 16 - 4;
+    bytes[len_at..len_at + 4].copy_from_slice(&0xffff_fff0_u32.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let result = read(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(Error::Decode { .. })));
+}
+
+#[test]
+fn data_len_partial_frame() {
+    let path = std::env::temp_dir().join("wav_optimizer_wav64_partial_frame.wav");
+    let spec = hound::WavSpec { channels: 1, sample_rate: 48000, bits_per_sample: 64, sample_format: hound::SampleFormat::Float };
+    write(&path, spec, &[0.5, -0.5, 0.25]).unwrap();
+    // made stereo afterwards, so the second frame is missing its right channel.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[22..24].copy_from_slice(&2_u16.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+
+    let result = read(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(Error::Decode { .. })));
+}